@@ -0,0 +1,166 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use serde::de::{MapAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+
+/// Service configuration read from JSON file given in `CONFIG_FILE` env variable, defaults to
+/// `$XDG_CONFIG_HOME/media-controls/config.json`. Missing file means default configuration.
+///
+/// ```json
+/// {
+///   "players": {
+///     "ignore": ["org.mpris.MediaPlayer2.kdeconnect.*", "chromium.instance*"],
+///     "aliases": { "Mozilla Firefox": "Living room" }
+///   }
+/// }
+/// ```
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct Config {
+    pub players: PlayersConfig,
+}
+
+impl Config {
+    pub fn load() -> Result<Self, anyhow::Error> {
+        let Some(path) = std::env::var_os("CONFIG_FILE")
+            .map(PathBuf::from)
+            .or_else(|| {
+                std::env::var_os("XDG_CONFIG_HOME")
+                    .map(PathBuf::from)
+                    .or_else(|| {
+                        std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config"))
+                    })
+                    .map(|config| config.join("media-controls").join("config.json"))
+            })
+        else {
+            return Ok(Self::default());
+        };
+
+        let config = match std::fs::read_to_string(&path) {
+            Ok(config) => config,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                tracing::info!("No config file at: {path:?}, using default config");
+                return Ok(Self::default());
+            }
+            Err(error) => {
+                return Err(error).with_context(|| format!("Failed to read config: {path:?}"));
+            }
+        };
+
+        tracing::info!("Using config file: {path:?}");
+        serde_json::from_str(&config).with_context(|| format!("Failed to parse config: {path:?}"))
+    }
+}
+
+/// Which players to show and how to name them. Patterns match either the bus name, the bus name
+/// without the `org.mpris.MediaPlayer2.` prefix or the player identity, `*` matches any
+/// characters.
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct PlayersConfig {
+    /// Patterns of players not to show.
+    pub ignore: Vec<String>,
+    /// Display names by pattern of players in the order given in the config, the first matching
+    /// pattern wins.
+    #[serde(deserialize_with = "ordered_entries")]
+    pub aliases: Vec<(String, String)>,
+}
+
+impl PlayersConfig {
+    pub fn is_ignored(&self, name: &str) -> bool {
+        self.ignore.iter().any(|pattern| matches(pattern, name))
+    }
+
+    /// Alias of the first pattern matching any of the names of the player.
+    pub fn alias(&self, names: &[&str]) -> Option<&str> {
+        self.aliases
+            .iter()
+            .find(|(pattern, _)| names.iter().any(|name| matches(pattern, name)))
+            .map(|(_, alias)| alias.as_str())
+    }
+}
+
+/// Deserialize JSON object to its entries in the order they are written in.
+fn ordered_entries<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<(String, String)>, D::Error> {
+    struct Entries;
+
+    impl<'de> Visitor<'de> for Entries {
+        type Value = Vec<(String, String)>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("an object of string values")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            let mut entries = Vec::new();
+            while let Some(entry) = map.next_entry()? {
+                entries.push(entry);
+            }
+            Ok(entries)
+        }
+    }
+
+    deserializer.deserialize_map(Entries)
+}
+
+/// Match value against pattern where `*` matches any, possibly empty, sequence of characters.
+fn matches(pattern: &str, value: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = value.strip_prefix(first) else {
+        return false;
+    };
+
+    let mut parts = parts.collect::<Vec<_>>();
+    let Some(last) = parts.pop() else {
+        // no wildcard, must match exactly
+        return rest.is_empty();
+    };
+
+    for part in parts {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn match_patterns() {
+        assert!(matches("firefox", "firefox"));
+        assert!(!matches("firefox", "firefox.instance1"));
+        assert!(matches("firefox*", "firefox.instance1"));
+        assert!(matches("*.instance*", "chromium.instance1234"));
+        assert!(matches(
+            "org.mpris.*.kdeconnect.*",
+            "org.mpris.MediaPlayer2.kdeconnect.mpris_1"
+        ));
+        assert!(matches("a*b*c", "abc"));
+        assert!(!matches("a*bc*c", "abc"));
+        assert!(matches("*", ""));
+    }
+
+    #[test]
+    fn first_matching_alias_wins() {
+        let config = serde_json::from_str::<PlayersConfig>(
+            r#"{"aliases": {"firefox*": "Firefox", "firefox.instance*": "Firefox instance", "*": "Any"}}"#,
+        )
+        .expect("config should parse");
+
+        assert_eq!(config.alias(&["firefox.instance1"]), Some("Firefox"));
+        assert_eq!(
+            config.alias(&["org.mpris.MediaPlayer2.vlc", "vlc"]),
+            Some("Any")
+        );
+    }
+}
@@ -1,3 +1,5 @@
+mod config;
+mod extract;
 mod media;
 mod pulseaudio;
 
@@ -13,10 +15,11 @@ use std::time::Duration;
 use anyhow::{Context, Error};
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
-use axum::{Router, routing};
+use axum::{Json, Router, routing};
 use hyper::server::conn::http1;
 use hyper_util::rt::TokioIo;
 use hyper_util::service::TowerToHyperService;
+use serde::Serialize;
 use thiserror::Error;
 use tokio::net::TcpListener;
 use tokio_rustls::TlsAcceptor;
@@ -29,25 +32,38 @@ use tracing_subscriber::{EnvFilter, FmtSubscriber};
 use zbus::Connection;
 use zbus::conn::Builder;
 
+use crate::config::Config;
+use crate::media::routes::MediaState;
+
 #[derive(Debug, Error)]
 enum ApiError {
-    #[error("{0}")]
+    #[error("{0:#}")]
     ListConnections(anyhow::Error),
-    #[error("{0}")]
+    #[error("{0:#}")]
     Players(anyhow::Error),
-    #[error("{0}")]
+    #[error("{0:#}")]
     Metadata(anyhow::Error),
-    #[error("{0}")]
+    #[error("{0:#}")]
+    Play(anyhow::Error),
+    #[error("{0:#}")]
+    Pause(anyhow::Error),
+    #[error("{0:#}")]
     PlayPause(anyhow::Error),
+    #[error("{0:#}")]
+    Stop(anyhow::Error),
+    #[error("{0:#}")]
+    OpenUri(anyhow::Error),
+    #[error("missing uri")]
+    MissingUri,
     #[error("missing offset")]
     MissingOffset,
     #[error("invalid offset")]
     InvalidOffset,
-    #[error("{0}")]
+    #[error("{0:#}")]
     Seek(anyhow::Error),
-    #[error("{0}")]
+    #[error("{0:#}")]
     Position(anyhow::Error),
-    #[error("{0}")]
+    #[error("{0:#}")]
     SetPosition(anyhow::Error),
     #[error("missing track id")]
     MissingTrackId,
@@ -55,42 +71,225 @@ enum ApiError {
     InvalidPosition,
     #[error("missing position")]
     MissingPosition,
-    #[error("{0}")]
+    #[error("{0:#}")]
     PlaybackStatus(anyhow::Error),
+    #[error("{0:#}")]
+    LoopStatus(anyhow::Error),
+    #[error("missing loop status")]
+    MissingLoopStatus,
+    #[error("invalid loop status, expected one of: None, Track, Playlist")]
+    InvalidLoopStatus,
+    #[error("{0:#}")]
+    Shuffle(anyhow::Error),
+    #[error("missing shuffle")]
+    MissingShuffle,
+    #[error("invalid shuffle, expected true or false")]
+    InvalidShuffle,
+    #[error("{0:#}")]
+    Rate(anyhow::Error),
+    #[error("missing rate")]
+    MissingRate,
+    #[error("invalid rate, expected positive number")]
+    InvalidRate,
+    #[error("{0}")]
+    Unsupported(String),
     #[error("failed to read image: {0}")]
     ReadImage(#[from] std::io::Error),
     #[error("failed to load image: {0}")]
     LoadImage(#[from] reqwest::Error),
-    #[error("{0}")]
+    #[error("{0:#}")]
     Volume(anyhow::Error),
-    #[error("{0}")]
+    #[error("{0:#}")]
+    PlayerVolume(anyhow::Error),
+    #[error("invalid volume, expected percent from 0 to 100")]
+    InvalidVolume,
+    #[error("{0:#}")]
     ConstructPlayer(anyhow::Error),
-    // #[error("{0}")]
-    // Next(anyhow::Error),
-    // #[error("{0}")]
-    // Previous(anyhow::Error),
+    #[error("unknown player: {0}")]
+    UnknownPlayer(String),
+    #[error("{0:#}")]
+    MediaPlayer(anyhow::Error),
+    #[error("{0:#}")]
+    Raise(anyhow::Error),
+    #[error("{0:#}")]
+    Quit(anyhow::Error),
+    #[error("{0:#}")]
+    Fullscreen(anyhow::Error),
+    #[error("invalid fullscreen, expected true or false")]
+    InvalidFullscreen,
+    #[error("{0:#}")]
+    TrackList(anyhow::Error),
+    #[error("invalid track id")]
+    InvalidTrackId,
+    #[error("invalid set as current, expected true or false")]
+    InvalidSetAsCurrent,
+    #[error("{0:#}")]
+    Playlists(anyhow::Error),
+    #[error("missing playlist id")]
+    MissingPlaylistId,
+    #[error("invalid playlist id")]
+    InvalidPlaylistId,
+    #[error("invalid index")]
+    InvalidIndex,
+    #[error("invalid max count")]
+    InvalidMaxCount,
+    #[error(
+        "invalid order, expected one of: Alphabetical, CreationDate, ModifiedDate, LastPlayDate, UserDefined"
+    )]
+    InvalidOrder,
+    #[error("invalid reverse order, expected true or false")]
+    InvalidReverseOrder,
+    #[error("{0:#}")]
+    Next(anyhow::Error),
+    #[error("{0:#}")]
+    Previous(anyhow::Error),
+    #[error("invalid request: {0}")]
+    InvalidRequest(String),
 }
 
-impl IntoResponse for ApiError {
-    fn into_response(self) -> axum::response::Response {
-        match self {
-            ApiError::MissingTrackId
-            | ApiError::InvalidPosition
-            | ApiError::MissingPosition
-            | ApiError::MissingOffset
-            | ApiError::InvalidOffset => {
-                (StatusCode::BAD_REQUEST, self.to_string()).into_response()
+tokio::task_local! {
+    /// Player the handled request is for as given by the client, reported in error bodies.
+    static REQUESTED_PLAYER: String;
+}
+
+/// Run the request handling with errors reported as errors of the player.
+async fn for_player<F: Future>(player: String, handle: F) -> F::Output {
+    REQUESTED_PLAYER.scope(player, handle).await
+}
+
+/// JSON body of error responses. Code is a stable machine-readable name of the error, player is
+/// the player the request was made for, if any.
+///
+/// ```json
+/// {"code": "player_not_found", "message": "unknown player: firefox", "player": "firefox"}
+/// ```
+#[derive(Serialize, Debug)]
+struct ErrorBody {
+    code: &'static str,
+    message: String,
+    player: Option<String>,
+}
+
+impl ApiError {
+    fn status_code(&self) -> StatusCode {
+        self.kind().0
+    }
+
+    fn code(&self) -> &'static str {
+        self.kind().1
+    }
+
+    /// Status code and error code of the error. Failed D-Bus calls are classified by the D-Bus
+    /// error, a vanished player is not found, a player not answering is a gateway timeout.
+    fn kind(&self) -> (StatusCode, &'static str) {
+        let (error, code) = match self {
+            ApiError::MissingUri => return (StatusCode::BAD_REQUEST, "missing_uri"),
+            ApiError::MissingOffset => return (StatusCode::BAD_REQUEST, "missing_offset"),
+            ApiError::InvalidOffset => return (StatusCode::BAD_REQUEST, "invalid_offset"),
+            ApiError::MissingTrackId => return (StatusCode::BAD_REQUEST, "missing_track_id"),
+            ApiError::InvalidPosition => return (StatusCode::BAD_REQUEST, "invalid_position"),
+            ApiError::MissingPosition => return (StatusCode::BAD_REQUEST, "missing_position"),
+            ApiError::MissingLoopStatus => {
+                return (StatusCode::BAD_REQUEST, "missing_loop_status");
+            }
+            ApiError::InvalidLoopStatus => {
+                return (StatusCode::BAD_REQUEST, "invalid_loop_status");
+            }
+            ApiError::MissingShuffle => return (StatusCode::BAD_REQUEST, "missing_shuffle"),
+            ApiError::InvalidShuffle => return (StatusCode::BAD_REQUEST, "invalid_shuffle"),
+            ApiError::MissingRate => return (StatusCode::BAD_REQUEST, "missing_rate"),
+            ApiError::InvalidRate => return (StatusCode::BAD_REQUEST, "invalid_rate"),
+            ApiError::InvalidFullscreen => return (StatusCode::BAD_REQUEST, "invalid_fullscreen"),
+            ApiError::InvalidTrackId => return (StatusCode::BAD_REQUEST, "invalid_track_id"),
+            ApiError::InvalidSetAsCurrent => {
+                return (StatusCode::BAD_REQUEST, "invalid_set_as_current");
+            }
+            ApiError::MissingPlaylistId => {
+                return (StatusCode::BAD_REQUEST, "missing_playlist_id");
+            }
+            ApiError::InvalidPlaylistId => {
+                return (StatusCode::BAD_REQUEST, "invalid_playlist_id");
+            }
+            ApiError::InvalidIndex => return (StatusCode::BAD_REQUEST, "invalid_index"),
+            ApiError::InvalidMaxCount => return (StatusCode::BAD_REQUEST, "invalid_max_count"),
+            ApiError::InvalidOrder => return (StatusCode::BAD_REQUEST, "invalid_order"),
+            ApiError::InvalidReverseOrder => {
+                return (StatusCode::BAD_REQUEST, "invalid_reverse_order");
+            }
+            ApiError::InvalidVolume => return (StatusCode::BAD_REQUEST, "invalid_volume"),
+            ApiError::InvalidRequest(_) => return (StatusCode::BAD_REQUEST, "invalid_request"),
+            ApiError::Unsupported(_) => return (StatusCode::CONFLICT, "unsupported"),
+            ApiError::UnknownPlayer(_) => return (StatusCode::NOT_FOUND, "player_not_found"),
+            ApiError::ReadImage(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                return (StatusCode::NOT_FOUND, "image_not_found");
+            }
+            ApiError::ReadImage(_) => {
+                return (StatusCode::INTERNAL_SERVER_ERROR, "image_read_failed");
+            }
+            ApiError::LoadImage(_) => return (StatusCode::BAD_GATEWAY, "image_fetch_failed"),
+            ApiError::ListConnections(error) => (error, "list_players_failed"),
+            ApiError::Players(error) => (error, "players_failed"),
+            ApiError::Metadata(error) => (error, "metadata_failed"),
+            ApiError::Play(error) => (error, "play_failed"),
+            ApiError::Pause(error) => (error, "pause_failed"),
+            ApiError::PlayPause(error) => (error, "play_pause_failed"),
+            ApiError::Stop(error) => (error, "stop_failed"),
+            ApiError::OpenUri(error) => (error, "open_uri_failed"),
+            ApiError::Seek(error) => (error, "seek_failed"),
+            ApiError::Position(error) => (error, "position_failed"),
+            ApiError::SetPosition(error) => (error, "set_position_failed"),
+            ApiError::PlaybackStatus(error) => (error, "playback_status_failed"),
+            ApiError::LoopStatus(error) => (error, "loop_status_failed"),
+            ApiError::Shuffle(error) => (error, "shuffle_failed"),
+            ApiError::Rate(error) => (error, "rate_failed"),
+            ApiError::Volume(error) => (error, "volume_failed"),
+            ApiError::PlayerVolume(error) => (error, "player_volume_failed"),
+            ApiError::ConstructPlayer(error) => (error, "player_connection_failed"),
+            ApiError::MediaPlayer(error) => (error, "media_player_failed"),
+            ApiError::Raise(error) => (error, "raise_failed"),
+            ApiError::Quit(error) => (error, "quit_failed"),
+            ApiError::Fullscreen(error) => (error, "fullscreen_failed"),
+            ApiError::TrackList(error) => (error, "track_list_failed"),
+            ApiError::Playlists(error) => (error, "playlists_failed"),
+            ApiError::Next(error) => (error, "next_failed"),
+            ApiError::Previous(error) => (error, "previous_failed"),
+        };
+
+        match error
+            .chain()
+            .find_map(|error| error.downcast_ref::<zbus::Error>())
+        {
+            Some(error) if media::player::is_service_unknown(error) => {
+                (StatusCode::NOT_FOUND, "player_not_found")
             }
-            _ => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()).into_response(),
-            // ApiError::Next(_) => {
-            //     (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()).into_response()
-            // }
-            // ApiError::Previous(_) => {
-            //     (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()).into_response()
+            Some(error) if media::player::is_timeout(error) => {
+                (StatusCode::GATEWAY_TIMEOUT, "player_timeout")
+            }
+            Some(error) if media::player::is_unsupported(error) => {
+                (StatusCode::CONFLICT, "unsupported")
+            }
+            _ => (StatusCode::INTERNAL_SERVER_ERROR, code),
+        }
+    }
+
+    fn body(&self) -> ErrorBody {
+        ErrorBody {
+            code: self.code(),
+            message: self.to_string(),
+            player: match self {
+                ApiError::UnknownPlayer(player) => Some(player.clone()),
+                _ => REQUESTED_PLAYER.try_with(Clone::clone).ok(),
+            },
         }
     }
 }
 
+impl IntoResponse for ApiError {
+    fn into_response(self) -> axum::response::Response {
+        (self.status_code(), Json(self.body())).into_response()
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
     tracing::subscriber::set_global_default(
@@ -105,6 +304,7 @@ async fn main() -> Result<(), anyhow::Error> {
             .context("Failed to create new journald tracing layer, not in linux")?;
         tracing_subscriber::registry().with(layer);
     }
+    let config = Config::load()?;
     let connection = Builder::session()
         .map_err(anyhow::Error::new)?
         .method_timeout(Duration::from_secs(5))
@@ -113,7 +313,7 @@ async fn main() -> Result<(), anyhow::Error> {
         .map_err(anyhow::Error::new)?;
 
     #[allow(unused_mut)]
-    let mut router = Router::new().nest("/api", api(Arc::new(connection)));
+    let mut router = Router::new().nest("/api", api(Arc::new(connection), config));
     #[cfg(feature = "embed-ui")]
     {
         router = router.fallback(ui::serve_ui);
@@ -215,11 +415,14 @@ fn rustls_server_config(
     Ok(Arc::new(config))
 }
 
-fn api(connection: Arc<Connection>) -> Router {
+fn api(connection: Arc<Connection>, config: Config) -> Router {
+    let media = MediaState::new(connection, Arc::new(config.players));
+
     #[allow(unused_mut)]
     let mut router = Router::new()
         .route("/status", routing::get(|| async { "OK" }))
-        .nest("/media", media::routes::media_api(connection))
+        .nest("/media", media::routes::media_api(media.clone()))
+        .route("/ws", routing::get(media::ws::connect).with_state(media))
         .route(
             "/volume",
             routing::get(pulseaudio::get_volume).post(pulseaudio::set_volume),
@@ -1,16 +1,74 @@
+pub mod active;
+pub mod hub;
+pub mod overview;
 pub mod player;
+pub mod playlists;
 pub mod routes;
+pub mod tracklist;
+pub mod ws;
 
 use std::convert::Infallible;
+use std::sync::Arc;
 use std::task::Poll;
+use std::time::Duration;
 
 use anyhow::Context;
 use axum::response::sse::Event;
-use futures::Stream;
+use futures::{Stream, StreamExt};
 use pin_project::pin_project;
+use serde::Serialize;
+use zbus::fdo::DBusProxy;
+use zbus::proxy::CacheProperties;
 use zbus::{Connection, Result};
 
-pub async fn get_players(connection: &Connection) -> anyhow::Result<impl Iterator<Item = String>> {
+use crate::config::PlayersConfig;
+use crate::media::player::MprisMediaPlayerProxy;
+
+const MPRIS_PREFIX: &str = "org.mpris.MediaPlayer2.";
+/// How many players are asked for their identity at once.
+const IDENTITY_CONCURRENCY: usize = 8;
+/// How long a player may take to tell its identity before it is reported unresponsive.
+const IDENTITY_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Player appearing or disappearing from the bus.
+pub enum PlayerChange {
+    Added(String),
+    Removed(String),
+}
+
+/// Whether the player is ignored by its bus name, with or without the MPRIS prefix.
+pub fn is_ignored(config: &PlayersConfig, player: &str) -> bool {
+    config.is_ignored(player) || config.is_ignored(short_name(player))
+}
+
+/// Whether the player is ignored by its bus name or its identity. Identity is only asked for if
+/// the config ignores any players, a player failing to tell its identity is not ignored.
+pub async fn is_player_ignored(
+    connection: &Connection,
+    config: &PlayersConfig,
+    player: &str,
+) -> bool {
+    if is_ignored(config, player) {
+        return true;
+    }
+    if config.ignore.is_empty() {
+        return false;
+    }
+
+    match tokio::time::timeout(IDENTITY_TIMEOUT, get_identity(connection, player)).await {
+        Ok(Ok(identity)) => config.is_ignored(&identity),
+        _ => false,
+    }
+}
+
+fn short_name(player: &str) -> &str {
+    player.strip_prefix(MPRIS_PREFIX).unwrap_or(player)
+}
+
+pub async fn get_players(
+    connection: &Connection,
+    config: &PlayersConfig,
+) -> anyhow::Result<impl Iterator<Item = String>> {
     const DEST: Option<&str> = Some("org.freedesktop.DBus");
 
     let message = connection
@@ -24,34 +82,147 @@ pub async fn get_players(connection: &Connection) -> anyhow::Result<impl Iterato
 
     Ok(names
         .into_iter()
-        .filter(|name| name.starts_with("org.mpris.MediaPlayer2")))
+        .filter(|name| name.starts_with(MPRIS_PREFIX))
+        .filter(|name| !is_ignored(config, name)))
+}
+
+pub async fn receive_player_changes(
+    connection: &Connection,
+) -> anyhow::Result<impl Stream<Item = PlayerChange> + use<>> {
+    let dbus = DBusProxy::new(connection)
+        .await
+        .context("Failed to create DBus proxy")?;
+    let changes = dbus
+        .receive_name_owner_changed()
+        .await
+        .context("Failed to subscribe NameOwnerChanged via DBus")?;
+
+    Ok(changes.filter_map(|signal| async move {
+        let args = match signal.args() {
+            Ok(args) => args,
+            Err(error) => {
+                tracing::warn!("Failed to read NameOwnerChanged signal: {error}");
+                return None;
+            }
+        };
+
+        let name = args.name().to_string();
+        if !name.starts_with(MPRIS_PREFIX) {
+            return None;
+        }
+
+        tracing::debug!(name, old_owner = ?args.old_owner(), new_owner = ?args.new_owner(), "Got NameOwnerChanged");
+        if args.new_owner().is_some() {
+            Some(PlayerChange::Added(name))
+        } else {
+            Some(PlayerChange::Removed(name))
+        }
+    }))
 }
 
 pub async fn get_identity(connection: &Connection, player: &str) -> Result<String> {
     tracing::info!("Getting player: {player} Identity");
 
-    let message = connection
-        .call_method(
-            Some(player),
-            "/org/mpris/MediaPlayer2",
-            Some("org.freedesktop.DBus.Properties"),
-            "Get",
-            &("org.mpris.MediaPlayer2", "Identity"),
-        )
+    let proxy = MprisMediaPlayerProxy::builder(connection)
+        .cache_properties(CacheProperties::No)
+        .destination(player)?
+        .build()
         .await?;
 
-    let body = message.body();
-    tracing::trace!("Got identity message, deserializing: {:#?}", &body.data());
-    tracing::trace!("Got identity body, deserializing: {:#?}", &body);
-    let (identity, _): (zvariant::Value, usize) = body.data().deserialize()?;
+    let identity = proxy.identity().await?;
     tracing::debug!(identity = ?&identity, "Got response");
-    let identity = identity
-        .downcast::<String>()
-        .expect("Should return String anyways");
 
     Ok(identity)
 }
 
+/// Player identity, bus name, whether the player failed to tell its identity in time and the
+/// stable id of the player if known. Serialized as a tuple
+/// `[identity, player, unresponsive, id]`, identity of unresponsive player falls back to its bus
+/// name without the MPRIS prefix.
+#[derive(Serialize, Debug)]
+pub struct PlayerIdentity(pub String, pub String, pub bool, pub Option<String>);
+
+impl PlayerIdentity {
+    pub fn player(&self) -> &str {
+        &self.1
+    }
+
+    pub fn with_id(self, id: Option<String>) -> Self {
+        Self(self.0, self.1, self.2, id)
+    }
+}
+
+/// Get identity of the player, `None` if the player is gone from the bus or ignored by the
+/// config. Identity is replaced with the alias from the config if any.
+pub async fn identify(
+    connection: &Connection,
+    config: &PlayersConfig,
+    player: String,
+) -> Option<PlayerIdentity> {
+    if is_ignored(config, &player) {
+        return None;
+    }
+    let identity = tokio::time::timeout(IDENTITY_TIMEOUT, get_identity(connection, &player)).await;
+
+    let alias = config
+        .alias(&[&player, short_name(&player)])
+        .map(ToString::to_string);
+
+    match identity {
+        Ok(Ok(identity)) if config.is_ignored(&identity) => {
+            tracing::debug!("Player: {player} identity: {identity} is ignored, skipping player");
+            None
+        }
+        Ok(Ok(identity)) => {
+            let name = config
+                .alias(&[&player, short_name(&player), &identity])
+                .map(ToString::to_string)
+                .unwrap_or(identity);
+            Some(PlayerIdentity(name, player, false, None))
+        }
+        Ok(Err(zbus::Error::FDO(error)))
+            if matches!(*error, zbus::fdo::Error::ServiceUnknown(_)) =>
+        {
+            tracing::debug!("Player: {player} is gone, skipping player");
+            None
+        }
+        Ok(Err(error)) => {
+            tracing::warn!(
+                "Error fetching identity for player: {player}, marking unresponsive, {error}"
+            );
+            Some(unresponsive(player, alias))
+        }
+        Err(_) => {
+            tracing::warn!(
+                "Player: {player} did not tell identity within {IDENTITY_TIMEOUT:?}, marking unresponsive"
+            );
+            Some(unresponsive(player, alias))
+        }
+    }
+}
+
+fn unresponsive(player: String, alias: Option<String>) -> PlayerIdentity {
+    let name = alias.unwrap_or_else(|| short_name(&player).to_string());
+
+    PlayerIdentity(name, player, true, None)
+}
+
+/// Identify the players concurrently, keeping the order of the players.
+pub fn identify_all(
+    connection: Connection,
+    config: Arc<PlayersConfig>,
+    players: Vec<String>,
+) -> impl Stream<Item = PlayerIdentity> + Send + use<> {
+    futures::stream::iter(players)
+        .map(move |player| {
+            let connection = connection.clone();
+            let config = config.clone();
+            async move { identify(&connection, &config, player).await }
+        })
+        .buffered(IDENTITY_CONCURRENCY)
+        .filter_map(futures::future::ready)
+}
+
 #[pin_project(project = SseEventProj)]
 enum SseEvent {
     Single(Option<Event>),
@@ -0,0 +1,384 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use futures::StreamExt;
+use serde::Serialize;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use zbus::Connection;
+
+use crate::config::PlayersConfig;
+use crate::media::PlayerChange;
+use crate::media::player::{MprisMediaPlayerProxy, MprisPlayerProxy, ProxyExt};
+
+const PLAYING: &str = "Playing";
+const PAUSED: &str = "Paused";
+
+/// Player the user most likely cares about.
+#[derive(Serialize, Debug, Clone, Eq, PartialEq)]
+pub struct ActivePlayer {
+    pub player: String,
+    /// Stable id of the player, see [`ActivePlayers::id`].
+    pub id: Option<String>,
+    pub status: String,
+}
+
+struct Tracked {
+    id: Option<String>,
+    /// Id without disambiguation, `None` until read from the player.
+    base_id: Option<String>,
+    /// Order of appearance, ids are assigned in this order.
+    order: u64,
+    status: String,
+    status_changed_at: Instant,
+    appeared_at: Instant,
+    task: JoinHandle<()>,
+}
+
+impl Tracked {
+    /// Players are ranked Playing > most recently Paused > most recently appeared.
+    fn rank(&self) -> (u8, Instant) {
+        match &*self.status {
+            PLAYING => (2, self.status_changed_at),
+            PAUSED => (1, self.status_changed_at),
+            _ => (0, self.appeared_at),
+        }
+    }
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Tracks playback status of all the players on the bus to tell which one of them is active,
+/// similar to `playerctld`. Also assigns the players ids staying the same across player restarts.
+pub struct ActivePlayers {
+    players: Mutex<HashMap<String, Tracked>>,
+    active: watch::Sender<Option<ActivePlayer>>,
+    next_order: AtomicU64,
+}
+
+impl ActivePlayers {
+    /// Start tracking players in background.
+    pub fn start(connection: Arc<Connection>, config: Arc<PlayersConfig>) -> Arc<Self> {
+        let active_players = Arc::new(Self {
+            players: Mutex::default(),
+            active: watch::Sender::new(None),
+            next_order: AtomicU64::new(0),
+        });
+
+        tokio::spawn(track(active_players.clone(), connection, config));
+
+        active_players
+    }
+
+    pub fn active(&self) -> Option<ActivePlayer> {
+        self.active.borrow().clone()
+    }
+
+    /// Receiver notified whenever the active player or its status changes.
+    pub fn subscribe(&self) -> watch::Receiver<Option<ActivePlayer>> {
+        self.active.subscribe()
+    }
+
+    /// Stable id of the player derived from its `DesktopEntry` or `Identity`, e.g. `firefox`.
+    /// Further instances of the same player get a number suffix, e.g. `firefox-2`, in order of
+    /// appearance, players present at start in order of bus name. `None` until the player and
+    /// the players appeared before it have told their desktop entry or identity.
+    pub fn id(&self, player: &str) -> Option<String> {
+        self.players
+            .lock()
+            .expect("players lock poisoned")
+            .get(player)
+            .and_then(|tracked| tracked.id.clone())
+    }
+
+    /// Resolve stable player id to the current bus name of the player.
+    pub fn resolve(&self, id: &str) -> Option<String> {
+        self.players
+            .lock()
+            .expect("players lock poisoned")
+            .iter()
+            .find(|(_, tracked)| tracked.id.as_deref() == Some(id))
+            .map(|(player, _)| player.clone())
+    }
+
+    /// Players currently playing.
+    pub fn playing(&self) -> Vec<String> {
+        self.players
+            .lock()
+            .expect("players lock poisoned")
+            .iter()
+            .filter(|(_, tracked)| tracked.status == PLAYING)
+            .map(|(player, _)| player.clone())
+            .collect()
+    }
+
+    async fn added(
+        self: &Arc<Self>,
+        connection: &Connection,
+        config: &PlayersConfig,
+        player: String,
+    ) {
+        if super::is_player_ignored(connection, config, &player).await {
+            tracing::debug!("Player: {player} is ignored, not tracking");
+            return;
+        }
+
+        let mut players = self.players.lock().expect("players lock poisoned");
+        // spawn while holding the lock so the status cannot be reported before the player is known
+        let task = tokio::spawn(track_status(
+            self.clone(),
+            connection.clone(),
+            player.clone(),
+        ));
+        let now = Instant::now();
+
+        players.insert(
+            player,
+            Tracked {
+                id: None,
+                base_id: None,
+                order: self.next_order.fetch_add(1, Ordering::Relaxed),
+                status: String::new(),
+                status_changed_at: now,
+                appeared_at: now,
+                task,
+            },
+        );
+        drop(players);
+        self.update();
+    }
+
+    fn removed(&self, player: &str) {
+        let mut players = self.players.lock().expect("players lock poisoned");
+        players.remove(player);
+        // players appeared later may have been waiting for this one
+        assign_ids(&mut players);
+        drop(players);
+        self.update();
+    }
+
+    fn status_changed(&self, player: &str, status: String) {
+        if let Some(tracked) = self
+            .players
+            .lock()
+            .expect("players lock poisoned")
+            .get_mut(player)
+            .filter(|tracked| tracked.status != status)
+        {
+            tracked.status = status;
+            tracked.status_changed_at = Instant::now();
+        }
+        self.update();
+    }
+
+    fn base_id_read(&self, player: &str, base_id: String) {
+        let mut players = self.players.lock().expect("players lock poisoned");
+        if let Some(tracked) = players.get_mut(player) {
+            tracked.base_id = Some(base_id);
+        }
+        assign_ids(&mut players);
+        drop(players);
+        self.update();
+    }
+
+    fn update(&self) {
+        let active = self
+            .players
+            .lock()
+            .expect("players lock poisoned")
+            .iter()
+            .max_by_key(|(_, tracked)| tracked.rank())
+            .map(|(player, tracked)| ActivePlayer {
+                player: player.clone(),
+                id: tracked.id.clone(),
+                status: tracked.status.clone(),
+            });
+
+        self.active.send_if_modified(|current| {
+            if *current != active {
+                tracing::debug!(?active, "Active player changed");
+                *current = active;
+                true
+            } else {
+                false
+            }
+        });
+    }
+}
+
+/// Assign ids to players in order of appearance, stopping at the first player whose base id is
+/// not known yet so that the number suffixes do not depend on which player answers first.
+fn assign_ids(players: &mut HashMap<String, Tracked>) {
+    let mut pending = players
+        .iter()
+        .filter(|(_, tracked)| tracked.id.is_none())
+        .map(|(player, tracked)| (tracked.order, player.clone()))
+        .collect::<Vec<_>>();
+    pending.sort();
+
+    for (_, player) in pending {
+        let Some(base) = players[&player].base_id.clone() else {
+            break;
+        };
+        let taken = |id: &str| {
+            players
+                .values()
+                .any(|tracked| tracked.id.as_deref() == Some(id))
+        };
+
+        let id = std::iter::once(base.clone())
+            .chain((2..).map(|number| format!("{base}-{number}")))
+            .find(|id| !taken(id))
+            .expect("there is always a free id");
+        tracing::debug!("Player: {player} has id: {id}");
+
+        if let Some(tracked) = players.get_mut(&player) {
+            tracked.id = Some(id);
+        }
+    }
+}
+
+async fn track(
+    active_players: Arc<ActivePlayers>,
+    connection: Arc<Connection>,
+    config: Arc<PlayersConfig>,
+) {
+    // subscribe before listing so no player is missed in between
+    let mut changes = match super::receive_player_changes(&connection).await {
+        Ok(changes) => changes.boxed(),
+        Err(error) => {
+            tracing::error!("Failed to track active player: {error:#}");
+            return;
+        }
+    };
+    match super::get_players(&connection, &config).await {
+        Ok(players) => {
+            // sorted so that ids of players present at start are the same across restarts
+            let mut players = players.collect::<Vec<_>>();
+            players.sort();
+            for player in players {
+                active_players.added(&connection, &config, player).await;
+            }
+        }
+        Err(error) => tracing::warn!("Failed to list initial players for active player: {error:#}"),
+    }
+
+    while let Some(change) = changes.next().await {
+        match change {
+            PlayerChange::Added(player) => active_players.added(&connection, &config, player).await,
+            PlayerChange::Removed(player) => active_players.removed(&player),
+        }
+    }
+}
+
+async fn track_status(active_players: Arc<ActivePlayers>, connection: Connection, player: String) {
+    // base id is read even if the status cannot be, players appeared later wait for it
+    active_players.base_id_read(&player, base_id(&connection, &player).await);
+
+    let proxy = match MprisPlayerProxy::try_create(&connection, &*player).await {
+        Ok(proxy) => proxy,
+        Err(error) => {
+            tracing::warn!("Failed to track player: {player} status: {error:#}");
+            return;
+        }
+    };
+    let mut status_changes = proxy.receive_playback_status_changed().await;
+
+    match proxy.playback_status().await {
+        Ok(status) => active_players.status_changed(&player, status),
+        Err(error) => tracing::debug!("Failed to get player: {player} PlaybackStatus: {error}"),
+    }
+
+    while let Some(changed) = status_changes.next().await {
+        match changed.get().await {
+            Ok(status) => active_players.status_changed(&player, status),
+            Err(error) => tracing::debug!("Failed to get player: {player} PlaybackStatus: {error}"),
+        }
+    }
+}
+
+/// Id of the player without disambiguation, `DesktopEntry` or `Identity` of the player falling
+/// back to the bus name without instance suffix.
+async fn base_id(connection: &Connection, player: &str) -> String {
+    let name = async {
+        match MprisMediaPlayerProxy::try_create(connection, player).await {
+            Ok(proxy) => match proxy.desktop_entry().await {
+                Ok(desktop_entry) if !desktop_entry.is_empty() => Some(desktop_entry),
+                _ => proxy.identity().await.ok(),
+            },
+            Err(error) => {
+                tracing::debug!("Failed to get player: {player} desktop entry: {error:#}");
+                None
+            }
+        }
+    };
+    // bounded as players appeared later wait for the id of this player
+    let name = tokio::time::timeout(super::IDENTITY_TIMEOUT * 2, name)
+        .await
+        .ok()
+        .flatten();
+    let name = name
+        .filter(|name| !name.trim().is_empty())
+        .unwrap_or_else(|| {
+            let name = super::short_name(player);
+            name.split(".instance").next().unwrap_or(name).to_string()
+        });
+
+    // lower case id can never be mistaken for a bus name with the MPRIS prefix
+    name.trim()
+        .to_lowercase()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '.' || c == '_' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracked(order: u64, base_id: Option<&str>) -> Tracked {
+        let now = Instant::now();
+        Tracked {
+            id: None,
+            base_id: base_id.map(ToString::to_string),
+            order,
+            status: String::new(),
+            status_changed_at: now,
+            appeared_at: now,
+            task: tokio::spawn(async {}),
+        }
+    }
+
+    fn id<'a>(players: &'a HashMap<String, Tracked>, player: &str) -> Option<&'a str> {
+        players[player].id.as_deref()
+    }
+
+    #[tokio::test]
+    async fn assign_ids_in_order_of_appearance() {
+        let mut players = HashMap::from([
+            ("vlc.instance1".to_string(), tracked(0, None)),
+            ("vlc.instance2".to_string(), tracked(1, Some("vlc"))),
+        ]);
+
+        // later player answered first, waits for the earlier one
+        assign_ids(&mut players);
+        assert_eq!(id(&players, "vlc.instance2"), None);
+
+        players.get_mut("vlc.instance1").unwrap().base_id = Some("vlc".to_string());
+        assign_ids(&mut players);
+        assert_eq!(id(&players, "vlc.instance1"), Some("vlc"));
+        assert_eq!(id(&players, "vlc.instance2"), Some("vlc-2"));
+    }
+}
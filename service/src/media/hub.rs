@@ -0,0 +1,705 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use futures::{StreamExt, TryFutureExt};
+use serde::Serialize;
+use tokio::sync::{Notify, broadcast};
+use tokio::task::JoinHandle;
+use tokio::time;
+use zbus::Connection;
+
+use crate::ApiError;
+use crate::media::PlayerChange;
+use crate::media::player::{
+    self, Capabilities, Metadata, MprisPlayerProxy, PositionUnit, ProxyExt,
+};
+use crate::pulseaudio::{self, PaCtl, PulseAudio};
+
+const PLAYING: &str = "Playing";
+/// How long to wait for the track to change after skipping the track.
+const REFRESH_WAIT: Duration = Duration::from_secs(1);
+
+/// Latest known state of a watched player.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct PlayerState {
+    pub metadata: Metadata,
+    pub status: String,
+    pub position: i64,
+    pub volume: String,
+    /// Volume percent of the player itself, `None` if the player does not support volume.
+    pub player_volume: Option<u32>,
+    /// `None` if the player does not support loop status.
+    pub loop_status: Option<String>,
+    /// `None` if the player does not support shuffle.
+    pub shuffle: Option<bool>,
+    pub rate: f64,
+    pub capabilities: Capabilities,
+}
+
+/// Change of a watched player broadcasted to all of its subscribers.
+#[derive(Clone, Debug)]
+pub enum PlayerEvent {
+    Metadata(Box<Metadata>),
+    Status(String),
+    Seeked(i64),
+    Position(i64),
+    Volume(String),
+    PlayerVolume(u32),
+    LoopStatus(String),
+    Shuffle(bool),
+    Rate(f64),
+    Capabilities(Capabilities),
+    /// Player disappeared from the bus. The watcher will not send any further events.
+    Closed(String),
+}
+
+/// Position units of the players by bus name, shared by the player watchers and seek. Entries
+/// are removed once the player disappears from the bus.
+#[derive(Default)]
+pub struct PositionUnits(Mutex<HashMap<String, Option<PositionUnit>>>);
+
+impl PositionUnits {
+    pub fn get(&self, player: &str) -> Option<PositionUnit> {
+        self.0
+            .lock()
+            .expect("position units lock poisoned")
+            .get(player)
+            .copied()
+            .flatten()
+    }
+
+    /// Whether the unit of the player has been probed already, even if it could not be detected.
+    pub fn is_probed(&self, player: &str) -> bool {
+        self.0
+            .lock()
+            .expect("position units lock poisoned")
+            .contains_key(player)
+    }
+
+    /// Record the unit of the player, `None` records an inconclusive probe without forgetting
+    /// an already detected unit.
+    pub fn insert(&self, player: &str, unit: Option<PositionUnit>) {
+        let mut units = self.0.lock().expect("position units lock poisoned");
+        match unit {
+            Some(unit) => {
+                if units.insert(player.to_string(), Some(unit)) != Some(Some(unit)) {
+                    tracing::debug!("Player: {player} reports position in: {unit:?}");
+                }
+            }
+            None => {
+                units.entry(player.to_string()).or_insert(None);
+            }
+        }
+    }
+
+    fn remove(&self, player: &str) {
+        self.0
+            .lock()
+            .expect("position units lock poisoned")
+            .remove(player);
+    }
+}
+
+/// Interpolates player position from the last position read from the player and the playback
+/// rate, so the position only needs to be read over D-Bus once in a while.
+struct PositionClock {
+    anchor: Option<(i64, Instant)>,
+    rate: f64,
+    /// Unit of the position, assumed micros until detected.
+    unit: Option<PositionUnit>,
+}
+
+impl PositionClock {
+    const RESYNC: Duration = Duration::from_secs(1);
+    /// Least amount of playback between two reads to detect the position unit from.
+    const DETECT_AFTER: Duration = Duration::from_millis(500);
+
+    fn new(position: i64, rate: f64, unit: Option<PositionUnit>) -> Self {
+        Self {
+            anchor: Some((position, Instant::now())),
+            rate,
+            unit,
+        }
+    }
+
+    /// Interpolated position, `None` when the position must be read from the player.
+    fn position(&self) -> Option<i64> {
+        let (position, at) = self.anchor.filter(|(_, at)| at.elapsed() < Self::RESYNC)?;
+        let per_second = self.unit.unwrap_or(PositionUnit::Micros).per_second() as f64;
+
+        Some(position + (at.elapsed().as_secs_f64() * per_second * self.rate) as i64)
+    }
+
+    /// Anchor to position read from the player. Returns the position unit if it got detected
+    /// from how much the position advanced since the previous anchor.
+    fn sync(&mut self, position: i64) -> Option<PositionUnit> {
+        let detected = match self.anchor {
+            Some((previous, at))
+                if self.unit.is_none() && self.rate.is_finite() && self.rate > 0.0 =>
+            {
+                Some(at.elapsed().mul_f64(self.rate))
+                    .filter(|played| *played >= Self::DETECT_AFTER)
+                    .and_then(|played| PositionUnit::detect(position - previous, played))
+            }
+            _ => None,
+        };
+        self.unit = self.unit.or(detected);
+        self.anchor = Some((position, Instant::now()));
+
+        detected
+    }
+
+    fn seeked(&mut self, position: i64) {
+        self.anchor = Some((position, Instant::now()));
+    }
+
+    fn set_rate(&mut self, rate: f64) {
+        self.rate = rate;
+        self.anchor = None;
+    }
+
+    fn reset(&mut self) {
+        self.anchor = None;
+    }
+}
+
+struct Watched {
+    id: u64,
+    subscribers: usize,
+    sender: broadcast::Sender<PlayerEvent>,
+    state: Arc<RwLock<PlayerState>>,
+    refresh: Arc<Notify>,
+    task: JoinHandle<()>,
+}
+
+impl Drop for Watched {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Shares a single watcher per player (keyed by bus name) with all subscribers. The watcher is
+/// started by the first subscriber and stopped once the last subscription is dropped.
+pub struct PlayerHub {
+    connection: Arc<Connection>,
+    players: Mutex<HashMap<String, Watched>>,
+    next_id: AtomicU64,
+    pub position_units: Arc<PositionUnits>,
+}
+
+impl PlayerHub {
+    pub fn new(connection: Arc<Connection>) -> Self {
+        let position_units = Arc::new(PositionUnits::default());
+        tokio::spawn(evict_position_units(
+            connection.clone(),
+            position_units.clone(),
+        ));
+
+        Self {
+            connection,
+            players: Mutex::default(),
+            next_id: AtomicU64::new(0),
+            position_units,
+        }
+    }
+
+    pub async fn subscribe(self: &Arc<Self>, player: &str) -> Result<Subscription, ApiError> {
+        if let Some(subscription) = self.join(player) {
+            return Ok(subscription);
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let watched = watch(&self.connection, player, id, self.position_units.clone()).await?;
+
+        let mut players = self.players.lock().expect("players lock poisoned");
+        // another subscriber might have started the watcher meanwhile
+        if players
+            .get(player)
+            .is_none_or(|existing| existing.task.is_finished())
+        {
+            players.insert(player.to_string(), watched);
+        }
+        let watched = players.get_mut(player).expect("player should be present");
+
+        Ok(self.subscription(player, watched))
+    }
+
+    /// Make player watcher re-read the metadata without waiting for D-Bus signals, e.g. after
+    /// skipping the track. Metadata is read until the track changes, for up to a second, as
+    /// players take a moment to switch the track.
+    pub fn refresh(&self, player: &str) {
+        if let Some(watched) = self
+            .players
+            .lock()
+            .expect("players lock poisoned")
+            .get(player)
+        {
+            watched.refresh.notify_one();
+        }
+    }
+
+    fn join(self: &Arc<Self>, player: &str) -> Option<Subscription> {
+        let mut players = self.players.lock().expect("players lock poisoned");
+
+        match players.get_mut(player) {
+            Some(watched) if !watched.task.is_finished() => {
+                Some(self.subscription(player, watched))
+            }
+            _ => None,
+        }
+    }
+
+    fn subscription(self: &Arc<Self>, player: &str, watched: &mut Watched) -> Subscription {
+        watched.subscribers += 1;
+        tracing::debug!(
+            "Player: {player} has {subscribers} subscribers",
+            subscribers = watched.subscribers
+        );
+
+        Subscription {
+            hub: self.clone(),
+            id: watched.id,
+            player: player.to_string(),
+            events: watched.sender.subscribe(),
+            state: watched.state.clone(),
+        }
+    }
+
+    fn unsubscribe(&self, player: &str, id: u64) {
+        let mut players = self.players.lock().expect("players lock poisoned");
+
+        let Some(watched) = players.get_mut(player).filter(|watched| watched.id == id) else {
+            return;
+        };
+        watched.subscribers -= 1;
+
+        if watched.subscribers == 0 {
+            tracing::debug!("Last subscriber of player: {player} left, stop watching");
+            players.remove(player);
+        }
+    }
+}
+
+pub struct Subscription {
+    hub: Arc<PlayerHub>,
+    id: u64,
+    player: String,
+    events: broadcast::Receiver<PlayerEvent>,
+    state: Arc<RwLock<PlayerState>>,
+}
+
+impl Subscription {
+    pub fn state(&self) -> PlayerState {
+        self.state.read().expect("state lock poisoned").clone()
+    }
+
+    /// Receive next player event, returns `None` when the player watcher has stopped.
+    pub async fn recv(&mut self) -> Option<PlayerEvent> {
+        loop {
+            match self.events.recv().await {
+                Ok(event) => return Some(event),
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::debug!(
+                        "Subscriber of player: {player} lagged behind by {skipped} events",
+                        player = self.player
+                    );
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.hub.unsubscribe(&self.player, self.id);
+    }
+}
+
+/// Forget position units of players once they disappear, bus names of restarted players often
+/// differ, e.g. by `.instance` suffix.
+async fn evict_position_units(connection: Arc<Connection>, units: Arc<PositionUnits>) {
+    let mut changes = match super::receive_player_changes(&connection).await {
+        Ok(changes) => changes.boxed(),
+        Err(error) => {
+            tracing::error!("Failed to track players for position units: {error:#}");
+            return;
+        }
+    };
+
+    while let Some(change) = changes.next().await {
+        if let PlayerChange::Removed(player) = change {
+            units.remove(&player);
+        }
+    }
+}
+
+async fn get_metadata(proxy: &MprisPlayerProxy<'_>, player: &str) -> Result<Metadata, ApiError> {
+    proxy
+        .metadata()
+        .map_err(anyhow::Error::new)
+        .and_then(|metadata| async { Ok(Metadata::try_from(metadata)?) })
+        .map_err(|error| {
+            ApiError::Metadata(error.context(format!("Failed to get player: {player} Metadata")))
+        })
+        .await
+}
+
+/// Read initial state of the player and spawn a task keeping it up to date.
+async fn watch(
+    connection: &Connection,
+    player: &str,
+    id: u64,
+    position_units: Arc<PositionUnits>,
+) -> Result<Watched, ApiError> {
+    tracing::info!("Start watching player: {player}");
+    // cached proxy receives the PropertiesChanged signals
+    let proxy = MprisPlayerProxy::try_create(connection, player)
+        .await
+        .map_err(ApiError::ConstructPlayer)?;
+    // uncached proxy for reading fresh values for players that are lazy in emitting signals
+    let fresh_proxy = MprisPlayerProxy::without_cache(connection, player)
+        .await
+        .map_err(ApiError::ConstructPlayer)?;
+
+    let metadata = get_metadata(&proxy, player).await?;
+    let status = proxy.playback_status().await.map_err(|error| {
+        ApiError::PlaybackStatus(
+            anyhow::Error::new(error)
+                .context(format!("Failed to get player: {player} PlaybackStatus")),
+        )
+    })?;
+    // some players, e.g. browsers, do not report position at all, stop asking them
+    let mut position_supported = true;
+    let initial_position = fresh_proxy
+        .position()
+        .await
+        .inspect_err(|error| {
+            tracing::debug!("Failed to get player: {player} Position, using 0: {error}");
+            position_supported = !player::is_unsupported(error);
+        })
+        .ok();
+    let position = initial_position.unwrap_or(0);
+    let volume = match pulseaudio::get_volume().await {
+        Ok(volume) => volume,
+        Err(error) => {
+            tracing::warn!(
+                "Failed to get initial volume for player: {player}, using empty string: error: {error:#?}"
+            );
+            String::new()
+        }
+    };
+
+    let player_volume = match proxy.volume().await {
+        Ok(volume) => Some(player::volume_to_percent(volume)),
+        Err(error) => {
+            tracing::debug!("Player: {player} does not support volume: {error}");
+            None
+        }
+    };
+
+    let loop_status = match proxy.loop_status().await {
+        Ok(loop_status) => Some(loop_status),
+        Err(error) => {
+            tracing::debug!("Player: {player} does not support loop status: {error}");
+            None
+        }
+    };
+    let shuffle = match proxy.shuffle().await {
+        Ok(shuffle) => Some(shuffle),
+        Err(error) => {
+            tracing::debug!("Player: {player} does not support shuffle: {error}");
+            None
+        }
+    };
+
+    let rate = proxy.rate().await.unwrap_or_else(|error| {
+        tracing::debug!("Player: {player} does not support rate, using 1.0: {error}");
+        1.0
+    });
+
+    let capabilities = Capabilities::read(&proxy).await;
+
+    let mut metadata_changes = proxy.receive_metadata_changed().await;
+    let mut capability_changes = futures::stream::select_all([
+        proxy.receive_can_play_changed().await.map(|_| ()).boxed(),
+        proxy.receive_can_pause_changed().await.map(|_| ()).boxed(),
+        proxy.receive_can_seek_changed().await.map(|_| ()).boxed(),
+        proxy
+            .receive_can_go_next_changed()
+            .await
+            .map(|_| ())
+            .boxed(),
+        proxy
+            .receive_can_go_previous_changed()
+            .await
+            .map(|_| ())
+            .boxed(),
+        proxy
+            .receive_can_control_changed()
+            .await
+            .map(|_| ())
+            .boxed(),
+    ]);
+    let mut rate_changes = proxy.receive_rate_changed().await;
+    let mut loop_status_changes = proxy.receive_loop_status_changed().await;
+    let mut shuffle_changes = proxy.receive_shuffle_changed().await;
+    let mut player_volume_changes = proxy.receive_volume_changed().await;
+    let mut status_changes = proxy.receive_playback_status_changed().await;
+    let mut seeked = proxy.receive_seeked().await.map_err(|error| {
+        ApiError::ConstructPlayer(anyhow::Error::new(error).context(format!(
+            "Failed to subscribe player: {player} Seeked signal"
+        )))
+    })?;
+    let mut owner_changes = proxy
+        .inner()
+        .receive_owner_changed()
+        .await
+        .map_err(|error| {
+            ApiError::ConstructPlayer(anyhow::Error::new(error).context(format!(
+                "Failed to subscribe player: {player} owner changes"
+            )))
+        })?;
+    let mut sink_changes = match PaCtl.subscribe_sink_changes().await {
+        Ok(sink_changes) => sink_changes,
+        Err(error) => {
+            tracing::warn!(
+                "Failed to subscribe volume changes for player: {player}, volume changes wont be sent: error: {error:#?}"
+            );
+            futures::stream::pending().boxed()
+        }
+    };
+
+    let mut playing = status == PLAYING;
+    let state = Arc::new(RwLock::new(PlayerState {
+        metadata,
+        status,
+        position,
+        volume,
+        player_volume,
+        loop_status,
+        shuffle,
+        rate,
+        capabilities,
+    }));
+    let (sender, _) = broadcast::channel(64);
+    let refresh = Arc::new(Notify::new());
+
+    let task = tokio::spawn({
+        let player = player.to_string();
+        let state = state.clone();
+        let sender = sender.clone();
+        let refresh = refresh.clone();
+        let mut position_interval = time::interval(Duration::from_millis(100));
+        let mut refresh_interval = time::interval(Duration::from_millis(100));
+        refresh_interval.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+        // metadata is polled until the track changes or the deadline passes
+        let mut refresh_until: Option<Instant> = None;
+        let mut clock = PositionClock::new(position, rate, position_units.get(&player));
+        if initial_position.is_none() {
+            clock.reset();
+        }
+        let mut track_id = state
+            .read()
+            .expect("state lock poisoned")
+            .metadata
+            .track_id
+            .clone();
+
+        let update = move |event: PlayerEvent| {
+            let mut state = state.write().expect("state lock poisoned");
+            let changed = match &event {
+                PlayerEvent::Metadata(metadata) if state.metadata != **metadata => {
+                    state.metadata = (**metadata).clone();
+                    true
+                }
+                PlayerEvent::Status(status) if state.status != *status => {
+                    state.status = status.clone();
+                    true
+                }
+                PlayerEvent::Volume(volume) if state.volume != *volume => {
+                    state.volume = volume.clone();
+                    true
+                }
+                PlayerEvent::PlayerVolume(volume) if state.player_volume != Some(*volume) => {
+                    state.player_volume = Some(*volume);
+                    true
+                }
+                PlayerEvent::LoopStatus(loop_status)
+                    if state.loop_status.as_ref() != Some(loop_status) =>
+                {
+                    state.loop_status = Some(loop_status.clone());
+                    true
+                }
+                PlayerEvent::Shuffle(shuffle) if state.shuffle != Some(*shuffle) => {
+                    state.shuffle = Some(*shuffle);
+                    true
+                }
+                PlayerEvent::Rate(rate) if state.rate != *rate => {
+                    state.rate = *rate;
+                    true
+                }
+                PlayerEvent::Capabilities(capabilities) if state.capabilities != *capabilities => {
+                    state.capabilities = *capabilities;
+                    true
+                }
+                PlayerEvent::Position(position) if state.position != *position => {
+                    state.position = *position;
+                    true
+                }
+                PlayerEvent::Seeked(position) => {
+                    state.position = *position;
+                    true
+                }
+                PlayerEvent::Closed(_) => true,
+                _ => false,
+            };
+            let playing = state.status == PLAYING;
+            drop(state);
+
+            if changed {
+                // no receivers is fine, last subscriber leaving will stop this task
+                let _ = sender.send(event);
+            }
+            playing
+        };
+
+        async move {
+            loop {
+                let event = tokio::select! {
+                    Some(owner) = owner_changes.next() => {
+                        if owner.is_some() {
+                            continue;
+                        }
+                        tracing::debug!("Player: {player} disappeared from the bus, stop watching");
+                        update(PlayerEvent::Closed(format!("Player: {player} closed")));
+                        return;
+                    }
+                    _ = refresh.notified() => {
+                        tracing::debug!("Player: {player} refresh requested, polling metadata for track change");
+                        refresh_until = Some(Instant::now() + REFRESH_WAIT);
+                        refresh_interval.reset_immediately();
+                        continue;
+                    }
+                    _ = refresh_interval.tick(), if refresh_until.is_some() => {
+                        match get_metadata(&fresh_proxy, &player).await {
+                            Ok(metadata) if metadata.track_id == track_id
+                                && refresh_until.is_some_and(|until| Instant::now() < until) => continue,
+                            metadata => {
+                                refresh_until = None;
+                                metadata.map(|metadata| PlayerEvent::Metadata(Box::new(metadata)))
+                            }
+                        }
+                    }
+                    Some(changed) = metadata_changes.next() => {
+                        match changed.get().await.map(Metadata::try_from) {
+                            Ok(Ok(metadata)) => Ok(PlayerEvent::Metadata(Box::new(metadata))),
+                            Ok(Err(error)) => {
+                                tracing::warn!("Skipping invalid player: {player} Metadata: {error}");
+                                continue;
+                            }
+                            Err(error) => Err(ApiError::Metadata(anyhow::Error::new(error).context(format!("Failed to get player: {player} Metadata")))),
+                        }
+                    }
+                    Some(changed) = status_changes.next() => {
+                        changed.get().await.map(PlayerEvent::Status).map_err(|error| {
+                            ApiError::PlaybackStatus(anyhow::Error::new(error).context(format!("Failed to get player: {player} PlaybackStatus")))
+                        })
+                    }
+                    Some(seeked) = seeked.next() => {
+                        match seeked.args() {
+                            Ok(args) => Ok(PlayerEvent::Seeked(args.position)),
+                            Err(error) => {
+                                tracing::warn!("Failed to read Seeked signal of player: {player}: {error}");
+                                continue;
+                            }
+                        }
+                    }
+                    _ = position_interval.tick(), if playing && position_supported => {
+                        match clock.position() {
+                            Some(position) => Ok(PlayerEvent::Position(position)),
+                            None => fresh_proxy.position().await
+                                .inspect(|position| {
+                                    if let Some(unit) = clock.sync(*position) {
+                                        position_units.insert(&player, Some(unit));
+                                    }
+                                })
+                                .inspect_err(|error| position_supported = !player::is_unsupported(error))
+                                .map(PlayerEvent::Position)
+                                .map_err(|error| {
+                                    ApiError::Position(anyhow::Error::new(error).context(format!("Failed to get player: {player} Position")))
+                                }),
+                        }
+                    }
+                    Some(()) = capability_changes.next() => {
+                        Ok(PlayerEvent::Capabilities(Capabilities::read(&proxy).await))
+                    }
+                    Some(changed) = rate_changes.next() => {
+                        changed.get().await.map(PlayerEvent::Rate).map_err(|error| {
+                            ApiError::Rate(anyhow::Error::new(error).context(format!("Failed to get player: {player} Rate")))
+                        })
+                    }
+                    Some(changed) = loop_status_changes.next() => {
+                        changed.get().await.map(PlayerEvent::LoopStatus).map_err(|error| {
+                            ApiError::LoopStatus(anyhow::Error::new(error).context(format!("Failed to get player: {player} LoopStatus")))
+                        })
+                    }
+                    Some(changed) = shuffle_changes.next() => {
+                        changed.get().await.map(PlayerEvent::Shuffle).map_err(|error| {
+                            ApiError::Shuffle(anyhow::Error::new(error).context(format!("Failed to get player: {player} Shuffle")))
+                        })
+                    }
+                    Some(changed) = player_volume_changes.next() => {
+                        match changed.get().await {
+                            Ok(volume) => Ok(PlayerEvent::PlayerVolume(player::volume_to_percent(volume))),
+                            Err(error) => {
+                                tracing::warn!("Failed to get player: {player} Volume, volume change wont be sent: error: {error}");
+                                continue;
+                            }
+                        }
+                    }
+                    Some(()) = sink_changes.next() => {
+                        match pulseaudio::get_volume().await {
+                            Ok(volume) => Ok(PlayerEvent::Volume(volume)),
+                            Err(error) => {
+                                tracing::warn!("Failed to get volume for player: {player}, volume change wont be sent: error: {error:#?}");
+                                continue;
+                            }
+                        }
+                    }
+                };
+
+                match &event {
+                    Ok(PlayerEvent::Seeked(position)) => clock.seeked(*position),
+                    Ok(PlayerEvent::Status(_)) => clock.reset(),
+                    Ok(PlayerEvent::Rate(rate)) => clock.set_rate(*rate),
+                    // position of the previous track must not be interpolated into the new one
+                    Ok(PlayerEvent::Metadata(metadata)) if metadata.track_id != track_id => {
+                        track_id = metadata.track_id.clone();
+                        clock.reset();
+                        // track change got reported, no need to poll for it any longer
+                        refresh_until = None;
+                    }
+                    _ => (),
+                }
+
+                match event {
+                    Ok(event) => playing = update(event),
+                    // player may just be slow to answer, only disappearing from the bus ends watching
+                    Err(error) => tracing::warn!("Skipping player: {player} change: {error}"),
+                }
+            }
+        }
+    });
+
+    Ok(Watched {
+        id,
+        subscribers: 0,
+        sender,
+        state,
+        refresh,
+        task,
+    })
+}
@@ -0,0 +1,108 @@
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::time::timeout;
+use zbus::Connection;
+
+use crate::config::PlayersConfig;
+use crate::media::player::{self, Capabilities, Metadata, MprisPlayerProxy, ProxyExt};
+
+/// How long a single player may take to answer before it is reported unresponsive.
+const PLAYER_TIMEOUT: Duration = Duration::from_millis(1500);
+
+/// Snapshot of a player for rendering "now playing" without further requests. Values the player
+/// fails to report are `None`.
+#[derive(Serialize, Debug)]
+pub struct PlayerOverview {
+    pub player: String,
+    pub identity: Option<String>,
+    pub metadata: Option<Metadata>,
+    pub status: Option<String>,
+    pub position: Option<i64>,
+    pub capabilities: Option<Capabilities>,
+    /// Volume percent of the player itself.
+    pub volume: Option<u32>,
+    /// Player did not answer within the timeout.
+    pub unresponsive: bool,
+}
+
+impl PlayerOverview {
+    fn unresponsive(player: String) -> Self {
+        Self {
+            player,
+            identity: None,
+            metadata: None,
+            status: None,
+            position: None,
+            capabilities: None,
+            volume: None,
+            unresponsive: true,
+        }
+    }
+}
+
+/// Read overview of all the given players concurrently. Identity is replaced with the alias from
+/// the config if any, the same as in the player list.
+pub async fn read_all(
+    connection: &Connection,
+    config: &PlayersConfig,
+    players: Vec<String>,
+) -> Vec<PlayerOverview> {
+    futures::future::join_all(players.into_iter().map(|player| async move {
+        let mut overview = match timeout(PLAYER_TIMEOUT, read(connection, &player)).await {
+            Ok(overview) => overview,
+            Err(_) => {
+                tracing::warn!(
+                    "Player: {player} did not respond within {PLAYER_TIMEOUT:?}, marking unresponsive"
+                );
+                PlayerOverview::unresponsive(player)
+            }
+        };
+
+        let player = overview.player.as_str();
+        let mut names = vec![player, super::short_name(player)];
+        names.extend(overview.identity.as_deref());
+        if let Some(alias) = config.alias(&names) {
+            overview.identity = Some(alias.to_string());
+        }
+
+        overview
+    }))
+    .await
+}
+
+async fn read(connection: &Connection, player: &str) -> PlayerOverview {
+    let proxy = match MprisPlayerProxy::without_cache(connection, player).await {
+        Ok(proxy) => proxy,
+        Err(error) => {
+            tracing::warn!("Failed to read player: {player} overview: {error}");
+            return PlayerOverview::unresponsive(player.to_string());
+        }
+    };
+
+    let (identity, metadata, status, position, capabilities, volume) = futures::join!(
+        super::get_identity(connection, player),
+        proxy.metadata(),
+        proxy.playback_status(),
+        proxy.position(),
+        Capabilities::read(&proxy),
+        proxy.volume(),
+    );
+
+    let metadata = metadata
+        .map_err(anyhow::Error::new)
+        .and_then(|metadata| Metadata::try_from(metadata).map_err(anyhow::Error::new))
+        .inspect_err(|error| tracing::debug!("Failed to get player: {player} metadata: {error}"))
+        .ok();
+
+    PlayerOverview {
+        player: player.to_string(),
+        identity: identity.ok(),
+        metadata,
+        status: status.ok(),
+        position: position.ok(),
+        capabilities: Some(capabilities),
+        volume: volume.ok().map(player::volume_to_percent),
+        unresponsive: false,
+    }
+}
@@ -1,77 +1,359 @@
 use std::collections::HashMap;
 use std::fmt::Display;
 use std::ops::{Deref, DerefMut};
+use std::time::Duration;
 
 use async_trait::async_trait;
 use futures::TryFutureExt;
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
 use zbus::names::BusName;
 use zbus::proxy::{Builder, CacheProperties};
-use zbus::zvariant::{OwnedObjectPath, Value};
-use zbus::{Connection, Result, proxy};
+use zbus::zvariant::{OwnedObjectPath, OwnedValue, Value};
+use zbus::{Connection, DBusError, Result, proxy};
+
+#[proxy(
+    interface = "org.mpris.MediaPlayer2",
+    default_path = "/org/mpris/MediaPlayer2"
+)]
+pub trait MprisMediaPlayer {
+    fn raise(&self) -> Result<()>;
+    fn quit(&self) -> Result<()>;
+
+    #[zbus(property)]
+    fn can_raise(&self) -> Result<bool>;
+
+    #[zbus(property)]
+    fn can_quit(&self) -> Result<bool>;
+
+    #[zbus(property)]
+    fn identity(&self) -> Result<String>;
+
+    #[zbus(property)]
+    fn desktop_entry(&self) -> Result<String>;
+
+    #[zbus(property)]
+    fn fullscreen(&self) -> Result<bool>;
+    #[zbus(property)]
+    fn set_fullscreen(&self, fullscreen: bool) -> Result<()>;
+
+    #[zbus(property)]
+    fn can_set_fullscreen(&self) -> Result<bool>;
+
+    #[zbus(property)]
+    fn supported_uri_schemes(&self) -> Result<Vec<String>>;
+
+    #[zbus(property)]
+    fn supported_mime_types(&self) -> Result<Vec<String>>;
+}
+
+impl MprisMediaPlayerProxy<'static> {
+    pub async fn try_create<'a>(
+        connection: &Connection,
+        destination: impl TryInto<BusName<'a>> + Display + Send,
+    ) -> std::result::Result<Self, anyhow::Error> {
+        uncached_proxy(connection, destination, "MediaPlayer2").await
+    }
+}
+
+/// Create proxy of an MPRIS interface of the player not caching properties, so every property
+/// read asks the player. Interface is the name used in the error message.
+pub async fn uncached_proxy<T>(
+    connection: &Connection,
+    destination: impl Display,
+    interface: &str,
+) -> std::result::Result<T, anyhow::Error>
+where
+    T: From<zbus::Proxy<'static>> + zbus::proxy::Defaults,
+{
+    async {
+        Builder::<T>::new(connection)
+            .cache_properties(CacheProperties::No)
+            .destination(destination.to_string())
+    }
+    .and_then(Builder::build)
+    .await
+    .map_err(|error| {
+        anyhow::Error::new(error).context(format!(
+            "Failed to create DBus {interface} connection MPRIS protocol for player: {destination}"
+        ))
+    })
+}
+
+/// Properties of the MPRIS root interface. Optional properties the player does not
+/// implement are left empty.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Eq, PartialEq)]
+pub struct MediaPlayerInfo {
+    pub identity: String,
+    pub desktop_entry: Option<String>,
+    pub can_raise: bool,
+    pub can_quit: bool,
+    pub fullscreen: Option<bool>,
+    pub can_set_fullscreen: bool,
+    pub supported_uri_schemes: Vec<String>,
+    pub supported_mime_types: Vec<String>,
+}
+
+impl MediaPlayerInfo {
+    pub async fn read(proxy: &MprisMediaPlayerProxy<'_>) -> Result<Self> {
+        let (
+            identity,
+            desktop_entry,
+            can_raise,
+            can_quit,
+            fullscreen,
+            can_set_fullscreen,
+            supported_uri_schemes,
+            supported_mime_types,
+        ) = futures::join!(
+            proxy.identity(),
+            proxy.desktop_entry(),
+            proxy.can_raise(),
+            proxy.can_quit(),
+            proxy.fullscreen(),
+            proxy.can_set_fullscreen(),
+            proxy.supported_uri_schemes(),
+            proxy.supported_mime_types(),
+        );
+
+        Ok(Self {
+            identity: identity?,
+            desktop_entry: desktop_entry.ok(),
+            can_raise: can_raise.unwrap_or_default(),
+            can_quit: can_quit.unwrap_or_default(),
+            fullscreen: fullscreen.ok(),
+            can_set_fullscreen: can_set_fullscreen.unwrap_or_default(),
+            supported_uri_schemes: supported_uri_schemes.unwrap_or_default(),
+            supported_mime_types: supported_mime_types.unwrap_or_default(),
+        })
+    }
+}
 
 #[proxy(
     interface = "org.mpris.MediaPlayer2.Player",
     default_path = "/org/mpris/MediaPlayer2"
 )]
 pub trait MprisPlayer {
+    fn play(&self) -> Result<()>;
+    fn pause(&self) -> Result<()>;
     fn play_pause(&self) -> Result<()>;
+    fn stop(&self) -> Result<()>;
+    fn open_uri(&self, uri: &str) -> Result<()>;
     fn seek(&self, offfset: i64) -> Result<()>;
     fn set_position(&self, track_id: OwnedObjectPath, offset: i64) -> Result<()>;
     fn next(&self) -> Result<()>;
     fn previous(&self) -> Result<()>;
 
     #[zbus(property)]
-    fn metadata(&self) -> Result<HashMap<String, Value<'static>>>;
+    fn metadata(&self) -> Result<OwnedValue>;
 
     #[zbus(property)]
     fn position(&self) -> Result<i64>;
 
     #[zbus(property)]
     fn playback_status(&self) -> Result<String>;
+
+    #[zbus(property)]
+    fn loop_status(&self) -> Result<String>;
+    #[zbus(property)]
+    fn set_loop_status(&self, loop_status: &str) -> Result<()>;
+
+    #[zbus(property)]
+    fn shuffle(&self) -> Result<bool>;
+    #[zbus(property)]
+    fn set_shuffle(&self, shuffle: bool) -> Result<()>;
+
+    #[zbus(property)]
+    fn rate(&self) -> Result<f64>;
+    #[zbus(property)]
+    fn set_rate(&self, rate: f64) -> Result<()>;
+    #[zbus(property)]
+    fn minimum_rate(&self) -> Result<f64>;
+    #[zbus(property)]
+    fn maximum_rate(&self) -> Result<f64>;
+
+    #[zbus(property)]
+    fn can_play(&self) -> Result<bool>;
+    #[zbus(property)]
+    fn can_pause(&self) -> Result<bool>;
+    #[zbus(property)]
+    fn can_seek(&self) -> Result<bool>;
+    #[zbus(property)]
+    fn can_go_next(&self) -> Result<bool>;
+    #[zbus(property)]
+    fn can_go_previous(&self) -> Result<bool>;
+    #[zbus(property)]
+    fn can_control(&self) -> Result<bool>;
+
+    #[zbus(property)]
+    fn volume(&self) -> Result<f64>;
+    #[zbus(property)]
+    fn set_volume(&self, volume: f64) -> Result<()>;
+
+    #[zbus(signal)]
+    fn seeked(&self, position: i64) -> Result<()>;
 }
 
 #[async_trait]
 pub trait ProxyExt {
-    async fn get_length(&self) -> std::result::Result<i64, zbus::Error>;
-
     async fn try_create<'a>(
         connection: &Connection,
         destination: impl TryInto<BusName<'a>> + Display + Send,
-    ) -> std::result::Result<MprisPlayerProxy<'_>, anyhow::Error> {
+    ) -> std::result::Result<MprisPlayerProxy<'static>, anyhow::Error> {
         MprisPlayerProxy::new(connection, destination.to_string())
             .await
-            .map_err(|error| anyhow::anyhow!("Failed to create DBus Player2 connection MPRIS protocol for player: {destination}: {error}"))
+            .map_err(|error| anyhow::Error::new(error).context(format!("Failed to create DBus Player2 connection MPRIS protocol for player: {destination}")))
     }
 
     async fn without_cache<'a>(
         connection: &'a Connection,
         destination: impl TryInto<BusName<'a>> + Display + Send + Sync,
     ) -> std::result::Result<WithoutCaching<MprisPlayerProxy<'static>>, anyhow::Error> {
-        async {
-            MprisPlayerProxy::builder(connection)
-                .cache_properties(CacheProperties::No)
-                .destination(destination.to_string())
+        uncached_proxy(connection, destination, "Player2")
+            .await
+            .map(WithoutCaching)
+    }
+}
+
+impl ProxyExt for MprisPlayerProxy<'static> {}
+
+/// What the player allows to be done with it. Flags the player fails to report are `false`.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Capabilities {
+    pub can_play: bool,
+    pub can_pause: bool,
+    pub can_seek: bool,
+    pub can_go_next: bool,
+    pub can_go_previous: bool,
+    pub can_control: bool,
+}
+
+impl Capabilities {
+    pub async fn read(proxy: &MprisPlayerProxy<'_>) -> Self {
+        let (can_play, can_pause, can_seek, can_go_next, can_go_previous, can_control) = futures::join!(
+            proxy.can_play(),
+            proxy.can_pause(),
+            proxy.can_seek(),
+            proxy.can_go_next(),
+            proxy.can_go_previous(),
+            proxy.can_control(),
+        );
+
+        Self {
+            can_play: can_play.unwrap_or_default(),
+            can_pause: can_pause.unwrap_or_default(),
+            can_seek: can_seek.unwrap_or_default(),
+            can_go_next: can_go_next.unwrap_or_default(),
+            can_go_previous: can_go_previous.unwrap_or_default(),
+            can_control: can_control.unwrap_or_default(),
         }
-        .and_then(Builder::build)
-        .await
-        .map(WithoutCaching)
-        .map_err(|error| anyhow::anyhow!("Failed to create DBus Player2 connection MPRIS protocol for player: {destination}: {error}"))
     }
 }
 
-#[async_trait]
-impl ProxyExt for MprisPlayerProxy<'static> {
-    async fn get_length(&self) -> std::result::Result<i64, zbus::Error> {
-        self.metadata()
-            .and_then(|meta| async {
-                let metadata = Into::<Metadata>::into(meta);
-                Ok(metadata.length)
-            })
-            .await
+pub const LOOP_STATUSES: [&str; 3] = ["None", "Track", "Playlist"];
+
+/// Whether the player responded with an error telling the operation or property is not
+/// supported by the player.
+pub fn is_unsupported(error: &zbus::Error) -> bool {
+    match error {
+        zbus::Error::FDO(error) => matches!(
+            **error,
+            zbus::fdo::Error::NotSupported(_)
+                | zbus::fdo::Error::PropertyReadOnly(_)
+                | zbus::fdo::Error::UnknownProperty(_)
+                | zbus::fdo::Error::UnknownMethod(_)
+                | zbus::fdo::Error::UnknownInterface(_)
+        ),
+        zbus::Error::MethodError(name, _, _) => name.ends_with("NotSupported"),
+        zbus::Error::Unsupported => true,
+        _ => false,
+    }
+}
+
+/// Name of the D-Bus error the call failed with, e.g. `org.freedesktop.DBus.Error.ServiceUnknown`.
+fn error_name(error: &zbus::Error) -> Option<String> {
+    match error {
+        zbus::Error::FDO(error) => Some(error.name().to_string()),
+        zbus::Error::MethodError(name, _, _) => Some(name.to_string()),
+        _ => None,
+    }
+}
+
+/// Whether the call failed because the player is not on the bus, e.g. it has quit.
+pub fn is_service_unknown(error: &zbus::Error) -> bool {
+    matches!(
+        error_name(error).as_deref(),
+        Some(
+            "org.freedesktop.DBus.Error.ServiceUnknown"
+                | "org.freedesktop.DBus.Error.NameHasNoOwner"
+        )
+    )
+}
+
+/// Whether the player did not reply in time.
+pub fn is_timeout(error: &zbus::Error) -> bool {
+    match error {
+        zbus::Error::InputOutput(error) => error.kind() == std::io::ErrorKind::TimedOut,
+        error => matches!(
+            error_name(error).as_deref(),
+            Some(
+                "org.freedesktop.DBus.Error.NoReply"
+                    | "org.freedesktop.DBus.Error.Timeout"
+                    | "org.freedesktop.DBus.Error.TimedOut"
+            )
+        ),
+    }
+}
+
+/// Unit the player reports its position in. MPRIS specifies microseconds, but some players
+/// report milliseconds instead.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum PositionUnit {
+    Micros,
+    Millis,
+}
+
+impl PositionUnit {
+    /// Amount of units in a second.
+    pub fn per_second(self) -> i64 {
+        match self {
+            PositionUnit::Micros => 1_000_000,
+            PositionUnit::Millis => 1_000,
+        }
+    }
+
+    /// Detect the unit from how much the position of a playing player advanced in the elapsed
+    /// time. `None` if the position did not advance.
+    pub fn detect(advanced: i64, elapsed: Duration) -> Option<Self> {
+        if advanced <= 0 {
+            return None;
+        }
+
+        // millis advance thousand times slower than micros, leave plenty of room for jitter
+        if advanced.saturating_mul(10) < elapsed.as_micros() as i64 {
+            Some(PositionUnit::Millis)
+        } else {
+            Some(PositionUnit::Micros)
+        }
+    }
+
+    /// Guess the unit from the track length when it cannot be detected, e.g. while paused.
+    /// Tracks shorter than 10 seconds in micros are rarer than tracks longer than 2.7 hours in
+    /// millis.
+    pub fn guess(length: i64) -> Self {
+        if length > 0 && length < 10_000_000 {
+            PositionUnit::Millis
+        } else {
+            PositionUnit::Micros
+        }
     }
 }
 
+/// Convert MPRIS volume (1.0 being 100%) to percent.
+pub fn volume_to_percent(volume: f64) -> u32 {
+    (volume.max(0.0) * 100.0).round() as u32
+}
+
 pub struct WithoutCaching<P: ProxyExt>(P);
 
 impl<P: ProxyExt> AsMut<P> for WithoutCaching<P> {
@@ -100,7 +382,7 @@ impl<P: ProxyExt> Deref for WithoutCaching<P> {
     }
 }
 
-#[derive(Serialize, Deserialize, Default, Debug, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
 pub struct Metadata {
     pub track_id: String,
     pub title: String,
@@ -108,85 +390,402 @@ pub struct Metadata {
     pub url: String,
     pub length: i64,
     pub artist: Vec<String>,
+    pub album: String,
+    pub album_artist: Vec<String>,
+    pub track_number: Option<i32>,
+    pub disc_number: Option<i32>,
+    pub genre: Vec<String>,
+    pub composer: Vec<String>,
+    pub lyricist: Vec<String>,
+    pub comment: Vec<String>,
+    pub content_created: String,
+    pub user_rating: Option<f64>,
+    pub auto_rating: Option<f64>,
+    pub use_count: Option<i32>,
+    /// Lyrics of the track.
+    pub as_text: String,
+    /// Metadata entries not covered by the fields above, e.g. player specific keys.
+    pub extras: HashMap<String, serde_json::Value>,
 }
 
-impl From<HashMap<String, Value<'static>>> for Metadata {
-    fn from(value: HashMap<String, Value>) -> Self {
-        let mut metadata = Metadata::default();
+#[derive(Debug, Error)]
+pub enum MetadataError {
+    #[error("expected metadata dictionary, got value of type: {0}")]
+    NotDictionary(String),
+}
 
-        if let Some(track_id) = value.get("mpris:trackid") {
-            let id = match track_id {
-                Value::Str(id) => id.as_str(),
-                Value::ObjectPath(id) => id.as_str(),
-                _ => {
-                    unreachable!(
-                        "Found a bug, should never get here, the track_id is ObjectPath or Str type"
-                    )
-                }
-            };
+/// Strip any variant wrappers around the value.
+fn unwrap_variant<'v, 'a>(value: &'v Value<'a>) -> &'v Value<'a> {
+    match value {
+        Value::Value(value) => unwrap_variant(value),
+        value => value,
+    }
+}
 
-            metadata.track_id = id.to_string();
-        }
+fn string(value: &Value) -> Option<String> {
+    match unwrap_variant(value) {
+        Value::Str(value) => Some(value.to_string()),
+        Value::ObjectPath(value) => Some(value.to_string()),
+        // some players send single value lists for plain strings
+        Value::Array(values) => values
+            .iter()
+            .map(string)
+            .collect::<Option<Vec<_>>>()
+            .map(|values| values.join(", ")),
+        _ => None,
+    }
+}
 
-        if let Some(title) = value.get("xesam:title") {
-            let Value::Str(title) = title else {
-                unreachable!("Found a bug, should never get here, the title is Str type")
-            };
+fn strings(value: &Value) -> Option<Vec<String>> {
+    match unwrap_variant(value) {
+        Value::Array(values) => values.iter().map(string).collect(),
+        // e.g. xesam:artist as plain string instead of list of strings
+        value => string(value).map(|value| vec![value]),
+    }
+}
 
-            metadata.title = title.to_string();
-        }
+fn integer(value: &Value) -> Option<i64> {
+    match unwrap_variant(value) {
+        Value::U8(value) => Some(i64::from(*value)),
+        Value::I16(value) => Some(i64::from(*value)),
+        Value::U16(value) => Some(i64::from(*value)),
+        Value::I32(value) => Some(i64::from(*value)),
+        Value::U32(value) => Some(i64::from(*value)),
+        Value::I64(value) => Some(*value),
+        Value::U64(value) => i64::try_from(*value).ok(),
+        Value::F64(value) if value.is_finite() => Some(*value as i64),
+        Value::Str(value) => value.trim().parse().ok(),
+        _ => None,
+    }
+}
 
-        if let Some(art_url) = value.get("mpris:artUrl") {
-            let Value::Str(art_url) = art_url else {
-                unreachable!("Found a bug, should never get here, the artUrl is Str type")
-            };
+fn int(value: &Value) -> Option<i32> {
+    integer(value).and_then(|value| i32::try_from(value).ok())
+}
 
-            metadata.art_url = art_url.to_string();
+fn float(value: &Value) -> Option<f64> {
+    match unwrap_variant(value) {
+        Value::F64(value) => Some(*value),
+        Value::Str(value) => value.trim().parse().ok(),
+        value => integer(value).map(|value| value as f64),
+    }
+}
+
+/// Convert D-Bus value to JSON, values not representable in JSON, e.g. file descriptors, are
+/// converted to `null`.
+pub fn to_json(value: &Value) -> serde_json::Value {
+    match value {
+        Value::U8(value) => (*value).into(),
+        Value::Bool(value) => (*value).into(),
+        Value::I16(value) => (*value).into(),
+        Value::U16(value) => (*value).into(),
+        Value::I32(value) => (*value).into(),
+        Value::U32(value) => (*value).into(),
+        Value::I64(value) => (*value).into(),
+        Value::U64(value) => (*value).into(),
+        Value::F64(value) => (*value).into(),
+        Value::Str(value) => value.as_str().into(),
+        Value::Signature(value) => value.to_string().into(),
+        Value::ObjectPath(value) => value.as_str().into(),
+        Value::Value(value) => to_json(value),
+        Value::Array(values) => values.iter().map(to_json).collect(),
+        Value::Dict(dict) => dict
+            .iter()
+            .map(|(key, value)| {
+                let key = string(key).unwrap_or_else(|| to_json(key).to_string());
+                (key, to_json(value))
+            })
+            .collect::<serde_json::Map<_, _>>()
+            .into(),
+        Value::Structure(structure) => structure.fields().iter().map(to_json).collect(),
+        _ => serde_json::Value::Null,
+    }
+}
+
+impl Metadata {
+    fn set(&mut self, key: &str, value: &Value) -> Option<()> {
+        match key {
+            "mpris:trackid" => self.track_id = string(value)?,
+            "mpris:length" => self.length = integer(value)?,
+            "mpris:artUrl" => self.art_url = string(value)?,
+            "xesam:title" => self.title = string(value)?,
+            "xesam:url" => self.url = string(value)?,
+            "xesam:artist" => self.artist = strings(value)?,
+            "xesam:album" => self.album = string(value)?,
+            "xesam:albumArtist" => self.album_artist = strings(value)?,
+            "xesam:trackNumber" => self.track_number = Some(int(value)?),
+            "xesam:discNumber" => self.disc_number = Some(int(value)?),
+            "xesam:genre" => self.genre = strings(value)?,
+            "xesam:composer" => self.composer = strings(value)?,
+            "xesam:lyricist" => self.lyricist = strings(value)?,
+            "xesam:comment" => self.comment = strings(value)?,
+            "xesam:contentCreated" => self.content_created = string(value)?,
+            "xesam:userRating" => self.user_rating = Some(float(value)?),
+            "xesam:autoRating" => self.auto_rating = Some(float(value)?),
+            "xesam:useCount" => self.use_count = Some(int(value)?),
+            "xesam:asText" => self.as_text = string(value)?,
+            key => {
+                self.extras.insert(key.to_string(), to_json(value));
+            }
         }
 
-        if let Some(url) = value.get("xesam:url") {
-            let Value::Str(url) = url else {
-                unreachable!("Found a bug, should never get here, the url is Str type")
-            };
+        Some(())
+    }
 
-            metadata.url = url.as_ref().to_string();
+    fn insert(&mut self, key: &str, value: &Value) {
+        if self.set(key, value).is_none() {
+            tracing::warn!(
+                "Skipping metadata entry: {key}, unexpected value of type: {signature}",
+                signature = unwrap_variant(value).value_signature()
+            );
         }
+    }
+}
+
+/// Parse metadata dictionary tolerating types differing from the specification, e.g.
+/// `xesam:artist` as a plain string or `mpris:length` as any integer. Entries that cannot be
+/// parsed are logged and skipped.
+impl TryFrom<&Value<'_>> for Metadata {
+    type Error = MetadataError;
+
+    fn try_from(value: &Value<'_>) -> std::result::Result<Self, Self::Error> {
+        let Value::Dict(dict) = unwrap_variant(value) else {
+            return Err(MetadataError::NotDictionary(
+                value.value_signature().to_string(),
+            ));
+        };
 
-        if let Some(length) = value.get("mpris:length") {
-            let length = match length {
-                Value::U64(length) => {
-                    i64::try_from(*length).expect("Failed to convert length from u64 to i64")
-                }
-                Value::I64(length) => *length,
-                _ => {
-                    unreachable!(
-                        "Found a bug, should never get here, the length is U64 or I64 type"
-                    )
-                }
+        let mut metadata = Metadata::default();
+
+        for (key, value) in dict.iter() {
+            let Some(key) = string(key) else {
+                tracing::warn!("Skipping metadata entry with non string key: {key:?}");
+                continue;
             };
 
-            metadata.length = length;
+            metadata.insert(&key, value);
         }
 
-        if let Some(artist) = value.get("xesam:artist") {
-            let Value::Array(artist) = artist else {
-                unreachable!("Found a bug, should never get here, the artist is Array of Str types")
-            };
+        Ok(metadata)
+    }
+}
 
-            metadata.artist = artist
-                .iter()
-                .map(|artist| {
-                    let Value::Str(artist) = artist else {
-                        unreachable!(
-                            "Found a bug, should never gethere, the artist should be a Str type"
-                        )
-                    };
-
-                    artist.to_string()
-                })
-                .collect();
+/// Metadata as `a{sv}` dictionary, e.g. in the `TrackList` interface. Parsed the same way as
+/// metadata given as a [`Value`].
+impl From<HashMap<String, OwnedValue>> for Metadata {
+    fn from(entries: HashMap<String, OwnedValue>) -> Self {
+        let mut metadata = Metadata::default();
+
+        for (key, value) in &entries {
+            metadata.insert(key, value);
         }
 
         metadata
     }
 }
+
+impl TryFrom<OwnedValue> for Metadata {
+    type Error = MetadataError;
+
+    fn try_from(value: OwnedValue) -> std::result::Result<Self, Self::Error> {
+        Metadata::try_from(&*value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zbus::zvariant::ObjectPath;
+
+    use super::*;
+
+    fn metadata<'a>(entries: impl IntoIterator<Item = (&'a str, Value<'a>)>) -> Value<'a> {
+        Value::from(entries.into_iter().collect::<HashMap<_, _>>())
+    }
+
+    #[test]
+    fn parse_spec_compliant_metadata() {
+        let value = metadata([
+            (
+                "mpris:trackid",
+                Value::from(ObjectPath::from_static_str_unchecked("/org/mpris/track/1")),
+            ),
+            ("mpris:length", Value::from(215_000_000_i64)),
+            ("xesam:title", Value::from("Song")),
+            ("xesam:artist", Value::from(vec!["Artist", "Other"])),
+            ("xesam:trackNumber", Value::from(3_i32)),
+            ("xesam:userRating", Value::from(0.8_f64)),
+        ]);
+
+        let metadata = Metadata::try_from(&value).expect("metadata should parse");
+
+        assert_eq!(metadata.track_id, "/org/mpris/track/1");
+        assert_eq!(metadata.length, 215_000_000);
+        assert_eq!(metadata.title, "Song");
+        assert_eq!(metadata.artist, vec!["Artist", "Other"]);
+        assert_eq!(metadata.track_number, Some(3));
+        assert_eq!(metadata.user_rating, Some(0.8));
+        assert!(metadata.extras.is_empty());
+    }
+
+    #[test]
+    fn parse_artist_as_plain_string() {
+        let value = metadata([
+            ("xesam:artist", Value::from("Artist")),
+            ("xesam:genre", Value::from("Rock")),
+        ]);
+
+        let metadata = Metadata::try_from(&value).expect("metadata should parse");
+
+        assert_eq!(metadata.artist, vec!["Artist"]);
+        assert_eq!(metadata.genre, vec!["Rock"]);
+    }
+
+    #[test]
+    fn parse_length_of_any_numeric_type() {
+        for length in [
+            Value::from(1_000_i32),
+            Value::from(1_000_u32),
+            Value::from(1_000_u64),
+            Value::from(1_000.0_f64),
+            Value::from("1000"),
+        ] {
+            let signature = length.value_signature().to_string();
+            let value = metadata([("mpris:length", length)]);
+
+            let metadata = Metadata::try_from(&value).expect("metadata should parse");
+
+            assert_eq!(metadata.length, 1_000, "length of type: {signature}");
+        }
+    }
+
+    #[test]
+    fn parse_nested_variants() {
+        let value = Value::Value(Box::new(metadata([(
+            "xesam:title",
+            Value::Value(Box::new(Value::from("Song"))),
+        )])));
+
+        let metadata = Metadata::try_from(&value).expect("metadata should parse");
+
+        assert_eq!(metadata.title, "Song");
+    }
+
+    #[test]
+    fn skip_unparsable_entries() {
+        let value = metadata([
+            ("xesam:title", Value::from(true)),
+            ("mpris:length", Value::from(u64::MAX)),
+            ("xesam:album", Value::from("Album")),
+        ]);
+
+        let metadata = Metadata::try_from(&value).expect("metadata should parse");
+
+        assert_eq!(metadata.title, "");
+        assert_eq!(metadata.length, 0);
+        assert_eq!(metadata.album, "Album");
+    }
+
+    #[test]
+    fn collect_unknown_keys_to_extras() {
+        let value = metadata([
+            ("xesam:title", Value::from("Song")),
+            ("custom:bitrate", Value::from(320_u32)),
+            ("custom:tags", Value::from(vec!["a", "b"])),
+        ]);
+
+        let metadata = Metadata::try_from(&value).expect("metadata should parse");
+
+        assert_eq!(metadata.extras.len(), 2);
+        assert_eq!(metadata.extras["custom:bitrate"], serde_json::json!(320));
+        assert_eq!(
+            metadata.extras["custom:tags"],
+            serde_json::json!(["a", "b"])
+        );
+    }
+
+    #[test]
+    fn detect_position_unit() {
+        let elapsed = Duration::from_millis(150);
+
+        assert_eq!(
+            PositionUnit::detect(148_000, elapsed),
+            Some(PositionUnit::Micros)
+        );
+        assert_eq!(
+            PositionUnit::detect(151, elapsed),
+            Some(PositionUnit::Millis)
+        );
+        assert_eq!(PositionUnit::detect(0, elapsed), None);
+
+        assert_eq!(PositionUnit::guess(215_000_000), PositionUnit::Micros);
+        assert_eq!(PositionUnit::guess(215_000), PositionUnit::Millis);
+        assert_eq!(PositionUnit::guess(0), PositionUnit::Micros);
+    }
+
+    #[test]
+    fn reject_non_dictionary_value() {
+        let value = Value::from("not a dictionary");
+
+        assert!(matches!(
+            Metadata::try_from(&value),
+            Err(MetadataError::NotDictionary(_))
+        ));
+    }
+
+    #[test]
+    fn parse_track_list_metadata_message() {
+        let track = HashMap::from([
+            (
+                "mpris:trackid",
+                Value::from(ObjectPath::from_static_str_unchecked(
+                    "/org/mpris/MediaPlayer2/Track/1",
+                )),
+            ),
+            ("xesam:title", Value::from("Title")),
+            ("xesam:artist", Value::from(vec!["Artist"])),
+        ]);
+        let message = zbus::Message::method_return(
+            &zbus::Message::method_call("/org/mpris/MediaPlayer2", "GetTracksMetadata")
+                .expect("method call should be valid")
+                .build(&())
+                .expect("method call should build")
+                .header(),
+        )
+        .expect("method return should be valid")
+        .build(&(vec![track],))
+        .expect("method return should build");
+
+        let body = message.body();
+        assert_eq!(body.signature().to_string(), "aa{sv}");
+        let tracks = body
+            .deserialize::<Vec<HashMap<String, OwnedValue>>>()
+            .expect("body should deserialize as track metadata");
+
+        let metadata = tracks.into_iter().map(Metadata::from).collect::<Vec<_>>();
+        assert_eq!(metadata.len(), 1);
+        assert_eq!(metadata[0].track_id, "/org/mpris/MediaPlayer2/Track/1");
+        assert_eq!(metadata[0].title, "Title");
+        assert_eq!(metadata[0].artist, vec!["Artist"]);
+    }
+
+    #[test]
+    fn classify_dbus_errors() {
+        let service_unknown = zbus::Error::FDO(Box::new(zbus::fdo::Error::ServiceUnknown(
+            "The name is not activatable".to_string(),
+        )));
+        assert!(is_service_unknown(&service_unknown));
+        assert!(!is_timeout(&service_unknown));
+
+        let timed_out = zbus::Error::InputOutput(std::sync::Arc::new(std::io::Error::new(
+            std::io::ErrorKind::TimedOut,
+            "timed out",
+        )));
+        assert!(is_timeout(&timed_out));
+        assert!(!is_service_unknown(&timed_out));
+
+        let no_reply = zbus::Error::FDO(Box::new(zbus::fdo::Error::NoReply(
+            "Did not receive a reply".to_string(),
+        )));
+        assert!(is_timeout(&no_reply));
+    }
+}
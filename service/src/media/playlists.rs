@@ -0,0 +1,78 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+use zbus::names::BusName;
+use zbus::zvariant::{ObjectPath, OwnedObjectPath};
+use zbus::{Connection, Result, proxy};
+
+use crate::media::player;
+
+/// Orderings defined by the MPRIS specification.
+pub const ORDERINGS: [&str; 5] = [
+    "Alphabetical",
+    "CreationDate",
+    "ModifiedDate",
+    "LastPlayDate",
+    "UserDefined",
+];
+
+/// Playlist as sent over D-Bus: id, name and icon.
+type RawPlaylist = (OwnedObjectPath, String, String);
+
+#[proxy(
+    interface = "org.mpris.MediaPlayer2.Playlists",
+    default_path = "/org/mpris/MediaPlayer2"
+)]
+pub trait MprisPlaylists {
+    fn activate_playlist(&self, playlist_id: &ObjectPath<'_>) -> Result<()>;
+    fn get_playlists(
+        &self,
+        index: u32,
+        max_count: u32,
+        order: &str,
+        reverse_order: bool,
+    ) -> Result<Vec<RawPlaylist>>;
+
+    #[zbus(property)]
+    fn playlist_count(&self) -> Result<u32>;
+
+    #[zbus(property)]
+    fn orderings(&self) -> Result<Vec<String>>;
+
+    #[zbus(property)]
+    fn active_playlist(&self) -> Result<(bool, RawPlaylist)>;
+}
+
+impl MprisPlaylistsProxy<'static> {
+    pub async fn try_create<'a>(
+        connection: &Connection,
+        destination: impl TryInto<BusName<'a>> + Display + Send,
+    ) -> std::result::Result<Self, anyhow::Error> {
+        player::uncached_proxy(connection, destination, "Playlists").await
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct Playlist {
+    pub id: String,
+    pub name: String,
+    pub icon: String,
+}
+
+impl From<RawPlaylist> for Playlist {
+    fn from((id, name, icon): RawPlaylist) -> Self {
+        Self {
+            id: id.to_string(),
+            name,
+            icon,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Playlists {
+    pub playlists: Vec<Playlist>,
+    pub active_playlist: Option<Playlist>,
+    pub playlist_count: u32,
+    pub orderings: Vec<String>,
+}
@@ -1,40 +1,138 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::convert::Infallible;
-use std::error::Error;
 use std::fmt::Display;
 use std::path::PathBuf;
-use std::sync::Arc;
-use std::time::Duration;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use anyhow::Context;
-use async_stream::stream;
 use axum::body::Body;
-use axum::extract::{Path, Query, State};
+use axum::extract::rejection::RawPathParamsRejection;
+use axum::extract::{FromRef, FromRequestParts, RawPathParams, Request, State};
+use axum::http::request::Parts;
+use axum::middleware::{self, Next};
 use axum::response::sse::Event;
-use axum::response::{Response, Sse};
+use axum::response::{IntoResponse, Response, Sse};
 use axum::{Json, Router, routing};
-use futures::{Stream, StreamExt, TryFutureExt, future};
+use futures::stream::FuturesUnordered;
+use futures::{Stream, StreamExt};
 use hyper::StatusCode;
 use tokio::fs;
 use tokio::sync::mpsc::Sender;
-use tokio::sync::oneshot;
-use tokio::time::{self, timeout};
+use tokio::time;
 use tokio_stream::wrappers::ReceiverStream;
 use zbus::Connection;
-use zvariant::OwnedObjectPath;
-
-use crate::media::SseEvent;
-use crate::media::player::{MprisPlayerProxy, ProxyExt};
-use crate::{ApiError, pulseaudio};
+use zvariant::{ObjectPath, OwnedObjectPath};
+
+use crate::ApiError;
+use crate::config::PlayersConfig;
+use crate::extract::{Form, Path, Query};
+use crate::media::active::{ActivePlayer, ActivePlayers};
+use crate::media::hub::{PlayerEvent, PlayerHub, PlayerState, Subscription};
+use crate::media::overview::{self, PlayerOverview};
+use crate::media::player::{
+    self, Capabilities, MediaPlayerInfo, MprisMediaPlayerProxy, MprisPlayerProxy, PositionUnit,
+    ProxyExt, WithoutCaching,
+};
+use crate::media::playlists::{self, MprisPlaylistsProxy, Playlist, Playlists};
+use crate::media::tracklist::{
+    self, MprisTrackListProxy, TrackAddedEvent, TrackListReplacedEvent, TrackMetadataChangedEvent,
+};
+use crate::media::{PlayerChange, PlayerIdentity, SseEvent};
+use crate::pulseaudio::VolumeForm;
 
 use super::player::Metadata;
 
-pub fn media_api(connection: Arc<Connection>) -> Router {
+#[derive(Clone)]
+pub struct MediaState {
+    pub(super) connection: Arc<Connection>,
+    pub(super) hub: Arc<PlayerHub>,
+    active: Arc<ActivePlayers>,
+    players_config: Arc<PlayersConfig>,
+    /// Players paused by pause all, to be resumed by resume.
+    resumable: Arc<Mutex<Vec<String>>>,
+}
+
+impl MediaState {
+    /// Create the state and start tracking the players on the bus.
+    pub fn new(connection: Arc<Connection>, players_config: Arc<PlayersConfig>) -> Self {
+        Self {
+            hub: Arc::new(PlayerHub::new(connection.clone())),
+            active: ActivePlayers::start(connection.clone(), players_config.clone()),
+            connection,
+            players_config,
+            resumable: Arc::default(),
+        }
+    }
+
+    /// Resolve bus name or stable id of the player to the current bus name of the player.
+    pub(super) fn resolve_player(&self, player: String) -> Result<String, ApiError> {
+        if player.starts_with(super::MPRIS_PREFIX) {
+            return Ok(player);
+        }
+
+        self.active
+            .resolve(&player)
+            .ok_or(ApiError::UnknownPlayer(player))
+    }
+}
+
+impl FromRef<MediaState> for Arc<Connection> {
+    fn from_ref(state: &MediaState) -> Self {
+        state.connection.clone()
+    }
+}
+
+impl FromRef<MediaState> for Arc<ActivePlayers> {
+    fn from_ref(state: &MediaState) -> Self {
+        state.active.clone()
+    }
+}
+
+impl FromRef<MediaState> for Arc<PlayersConfig> {
+    fn from_ref(state: &MediaState) -> Self {
+        state.players_config.clone()
+    }
+}
+
+/// Player path parameter accepting either the bus name or the stable id of the player,
+/// resolved to the current bus name of the player.
+pub(super) struct PlayerName(pub(super) String);
+
+impl FromRequestParts<MediaState> for PlayerName {
+    type Rejection = Response;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &MediaState,
+    ) -> Result<Self, Self::Rejection> {
+        let Path(player) = Path::<String>::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+
+        state
+            .resolve_player(player)
+            .map(Self)
+            .map_err(IntoResponse::into_response)
+    }
+}
+
+pub fn media_api(state: MediaState) -> Router {
     Router::new()
         .route("/players", routing::get(get_players))
         .route("/players-stream", routing::get(get_players_stream))
+        .route("/players-sse", routing::get(get_players_sse))
+        .route("/overview", routing::get(get_overview))
+        .route("/active", routing::get(get_active))
+        .route("/active-sse", routing::get(get_active_sse))
+        .route("/pause-all", routing::post(pause_all))
+        .route("/resume", routing::get(get_resumable).post(resume))
         .route("/metadata/{player}", routing::get(get_metadata))
+        .route("/play/{player}", routing::post(play))
+        .route("/pause/{player}", routing::post(pause))
         .route("/play_pause/{player}", routing::post(play_pause))
+        .route("/stop/{player}", routing::post(stop))
+        .route("/open_uri/{player}", routing::post(open_uri))
         .route("/seek/{player}", routing::post(seek))
         .route(
             "/position/{player}",
@@ -42,242 +140,1493 @@ pub fn media_api(connection: Arc<Connection>) -> Router {
         )
         .route("/position-sse/{player}", routing::get(get_positon_sse))
         .route("/status/{player}", routing::get(get_playback_status))
+        .route(
+            "/volume/{player}",
+            routing::get(get_player_volume).post(set_player_volume),
+        )
+        .route(
+            "/loop_status/{player}",
+            routing::get(get_loop_status).post(set_loop_status),
+        )
+        .route(
+            "/shuffle/{player}",
+            routing::get(get_shuffle).post(set_shuffle),
+        )
+        .route("/capabilities/{player}", routing::get(get_capabilities))
+        .route("/rate/{player}", routing::get(get_rate).post(set_rate))
+        .route("/info/{player}", routing::get(get_info))
+        .route("/raise/{player}", routing::post(raise))
+        .route("/quit/{player}", routing::post(quit))
+        .route(
+            "/fullscreen/{player}",
+            routing::get(get_fullscreen).post(set_fullscreen),
+        )
+        .route("/tracklist/{player}", routing::get(get_tracklist))
+        .route("/tracklist/goto/{player}", routing::post(tracklist_goto))
+        .route("/tracklist/add/{player}", routing::post(tracklist_add))
+        .route(
+            "/tracklist/remove/{player}",
+            routing::post(tracklist_remove),
+        )
+        .route("/tracklist-sse/{player}", routing::get(get_tracklist_sse))
+        .route(
+            "/playlists/{player}",
+            routing::get(get_playlists).post(activate_playlist),
+        )
         .route("/image/{url}", routing::get(get_image))
         .route("/player-sse/{player}", routing::get(get_player_sse))
-        // .route("/next/{player}", routing::post(next))
-        // .route("/previous/{player}", routing::post(previous))
-        .with_state(connection)
+        .route("/next/{player}", routing::post(next))
+        .route("/previous/{player}", routing::post(previous))
+        .route_layer(middleware::from_fn(requested_player))
+        .with_state(state)
+}
+
+/// Report errors of requests for a player with the player as given by the client.
+async fn requested_player(
+    params: Result<RawPathParams, RawPathParamsRejection>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let player = params.ok().and_then(|params| {
+        params
+            .iter()
+            .find(|(name, _)| *name == "player")
+            .map(|(_, player)| player.to_string())
+    });
+
+    match player {
+        Some(player) => crate::for_player(player, next.run(request)).await,
+        None => next.run(request).await,
+    }
+}
+
+async fn get_players(
+    State(connection): State<Arc<Connection>>,
+    State(config): State<Arc<PlayersConfig>>,
+    State(active): State<Arc<ActivePlayers>>,
+) -> Result<Json<Vec<PlayerIdentity>>, ApiError> {
+    let players = super::get_players(&connection, &config)
+        .await
+        .map_err(ApiError::ListConnections)?
+        .collect::<Vec<String>>();
+
+    let player_identities = super::identify_all(Connection::clone(&connection), config, players)
+        .map(|player_identity| {
+            let id = active.id(player_identity.player());
+            player_identity.with_id(id)
+        })
+        .collect::<Vec<PlayerIdentity>>()
+        .await;
+
+    Ok(Json(player_identities))
+}
+
+// for a reference, UI cannot handle streams as of now
+async fn get_players_stream(
+    State(connection): State<Arc<Connection>>,
+    State(config): State<Arc<PlayersConfig>>,
+    State(active): State<Arc<ActivePlayers>>,
+) -> Result<Response, ApiError> {
+    let players = super::get_players(&connection, &config)
+        .await
+        .map_err(ApiError::ListConnections)?
+        .collect::<Vec<String>>();
+
+    let player_identities = super::identify_all(Connection::clone(&connection), config, players)
+        .map(move |player_identity| {
+            let id = active.id(player_identity.player());
+            let mut json = serde_json::to_string(&player_identity.with_id(id))
+                .expect("Should be JSON serializable anyways");
+            json.push('\n');
+            json
+        })
+        .map(Ok::<String, Infallible>);
+
+    let response = Response::builder()
+        .header("content-type", "application/json+stream")
+        .status(StatusCode::OK)
+        .body(Body::from_stream(player_identities))
+        .map_err(|error| {
+            ApiError::Players(anyhow::Error::new(error).context("Error creating players body"))
+        })?;
+
+    Ok(response)
+}
+
+async fn get_players_sse(
+    State(connection): State<Arc<Connection>>,
+    State(config): State<Arc<PlayersConfig>>,
+    State(active): State<Arc<ActivePlayers>>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    tracing::info!("Get players SSE");
+    // subscribe before listing so no player is missed in between
+    let mut changes = match super::receive_player_changes(&connection).await {
+        Ok(changes) => changes.boxed(),
+        Err(error) => {
+            return Sse::new(SseEvent::Single(Some(
+                Event::default().event("error").data(error.to_string()),
+            )));
+        }
+    };
+    let players = match super::get_players(&connection, &config).await {
+        Ok(players) => players.map(PlayerChange::Added).collect::<Vec<_>>(),
+        Err(error) => {
+            return Sse::new(SseEvent::Single(Some(
+                Event::default().event("error").data(error.to_string()),
+            )));
+        }
+    };
+
+    let mut keepalive_interval = time::interval(Duration::from_secs(20));
+    let (tx, rx) = tokio::sync::mpsc::channel(30);
+
+    tokio::spawn(async move {
+        let mut initial = futures::stream::iter(players);
+        // players sent as added, others are either gone already or ignored
+        let mut added = HashSet::new();
+        // identified concurrently so a slow player does not hold up events of other players
+        let mut identifying = HashSet::new();
+        let mut identities = FuturesUnordered::new();
+
+        loop {
+            let change = tokio::select! {
+                // initial players first, then live changes
+                biased;
+                _ = tx.closed() => break,
+                Some(change) = initial.next() => change,
+                Some(change) = changes.next() => change,
+                Some((player, player_identity)) = identities.next() => {
+                    // player might have disappeared while being identified
+                    if !identifying.remove(&player) {
+                        continue;
+                    }
+                    let Some(player_identity): Option<PlayerIdentity> = player_identity else {
+                        continue;
+                    };
+                    let id = active.id(player_identity.player());
+                    added.insert(player);
+
+                    let event = Event::default().event("player-added").data(
+                        serde_json::to_string(&player_identity.with_id(id))
+                            .expect("Should be JSON serializable anyways"),
+                    );
+                    if tx.send(event).await.is_err() {
+                        tracing::debug!("Broke pipe, reciver is not present");
+                        break;
+                    }
+                    continue;
+                }
+                _ = keepalive_interval.tick() => {
+                    if tx.send(Event::default().event("keepalive").comment("")).await.is_err() {
+                        tracing::debug!("Broke pipe, failed to send keepalive, reciver is not present");
+                        break;
+                    }
+                    continue;
+                }
+            };
+
+            let event = match change {
+                PlayerChange::Added(player) => {
+                    // owner of a known name changed, the player is already added
+                    if added.contains(&player) || !identifying.insert(player.clone()) {
+                        continue;
+                    }
+                    let (connection, config) = (&connection, &config);
+                    identities.push(async move {
+                        let player_identity =
+                            super::identify(connection, config, player.clone()).await;
+                        (player, player_identity)
+                    });
+                    continue;
+                }
+                PlayerChange::Removed(player) => {
+                    identifying.remove(&player);
+                    if !added.remove(&player) {
+                        continue;
+                    }
+                    Event::default().event("player-removed").data(player)
+                }
+            };
+
+            if tx.send(event).await.is_err() {
+                tracing::debug!("Broke pipe, reciver is not present");
+                break;
+            }
+        }
+    });
+
+    let stream = ReceiverStream::new(rx).map(Ok::<Event, Infallible>);
+
+    Sse::new(SseEvent::Multi(Box::new(stream)))
+}
+
+async fn get_overview(
+    State(connection): State<Arc<Connection>>,
+    State(config): State<Arc<PlayersConfig>>,
+) -> Result<Json<Vec<PlayerOverview>>, ApiError> {
+    tracing::info!("Get players overview");
+    let players = super::get_players(&connection, &config)
+        .await
+        .map_err(ApiError::ListConnections)?;
+    let players = futures::stream::iter(players)
+        .map(|player| async {
+            let ignored = super::is_player_ignored(&connection, &config, &player).await;
+            (!ignored).then_some(player)
+        })
+        .buffered(super::IDENTITY_CONCURRENCY)
+        .filter_map(futures::future::ready)
+        .collect::<Vec<String>>()
+        .await;
+
+    Ok(Json(
+        overview::read_all(&connection, &config, players).await,
+    ))
+}
+
+async fn get_active(State(state): State<MediaState>) -> Json<Option<ActivePlayer>> {
+    tracing::info!("Get active player");
+
+    Json(state.active.active())
+}
+
+async fn get_active_sse(
+    State(state): State<MediaState>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    tracing::info!("Get active player SSE");
+    let mut active = state.active.subscribe();
+
+    let mut keepalive_interval = time::interval(Duration::from_secs(20));
+    let (tx, rx) = tokio::sync::mpsc::channel(30);
+
+    tokio::spawn(async move {
+        // send the current active player first
+        active.mark_changed();
+
+        loop {
+            let event = tokio::select! {
+                _ = keepalive_interval.tick() => Event::default().event("keepalive").comment(""),
+                _ = tx.closed() => break,
+                changed = active.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                    let player = active.borrow_and_update().clone();
+                    Event::default().event("active").data(
+                        serde_json::to_string(&player).expect("active player should serialize to JSON"),
+                    )
+                }
+            };
+
+            if tx.send(event).await.is_err() {
+                tracing::debug!("Broke pipe, reciver is not present");
+                break;
+            }
+        }
+    });
+
+    let stream = ReceiverStream::new(rx).map(Ok::<Event, Infallible>);
+
+    Sse::new(SseEvent::Multi(Box::new(stream)))
+}
+
+async fn get_metadata(
+    State(connection): State<Arc<Connection>>,
+    PlayerName(player): PlayerName,
+) -> Result<Json<Metadata>, ApiError> {
+    tracing::info!("Get player metadata: {}", &player);
+    let con = connection.as_ref();
+
+    let proxy = MprisPlayerProxy::try_create(con, &*player)
+        .await
+        .map_err(ApiError::ConstructPlayer)?;
+
+    let meta = proxy.metadata().await.map_err(|error| {
+        ApiError::Metadata(anyhow::Error::new(error).context("Failed to get player metadata"))
+    })?;
+
+    tracing::debug!(metadata = ?&meta, "Before from conversion");
+
+    let metadata = Metadata::try_from(meta).map_err(|error| {
+        ApiError::Metadata(
+            anyhow::Error::new(error).context(format!("Failed to parse player: {player} metadata")),
+        )
+    })?;
+
+    tracing::debug!(metadata = ?&metadata);
+
+    Ok(Json(metadata))
+}
+
+pub(super) async fn play(
+    State(connection): State<Arc<Connection>>,
+    PlayerName(player): PlayerName,
+) -> Result<(), ApiError> {
+    tracing::info!("Play: {}", &player);
+    let con = connection.as_ref();
+
+    let proxy = MprisPlayerProxy::try_create(con, &*player)
+        .await
+        .map_err(ApiError::ConstructPlayer)?;
+    ensure_capability(proxy.can_play().await, &player, "CanPlay")?;
+
+    proxy.play().await.map_err(|error| {
+        ApiError::Play(anyhow::Error::new(error).context(format!("Play player: {player}")))
+    })?;
+
+    Ok(())
+}
+
+pub(super) async fn pause(
+    State(connection): State<Arc<Connection>>,
+    PlayerName(player): PlayerName,
+) -> Result<(), ApiError> {
+    tracing::info!("Pause: {}", &player);
+    let con = connection.as_ref();
+
+    let proxy = MprisPlayerProxy::try_create(con, &*player)
+        .await
+        .map_err(ApiError::ConstructPlayer)?;
+    ensure_capability(proxy.can_pause().await, &player, "CanPause")?;
+
+    proxy.pause().await.map_err(|error| {
+        ApiError::Pause(anyhow::Error::new(error).context(format!("Pause player: {player}")))
+    })?;
+
+    Ok(())
+}
+
+/// Pause all playing players and remember them so they can be resumed later.
+async fn pause_all(State(state): State<MediaState>) -> Json<Vec<String>> {
+    let players = state.active.playing();
+    tracing::info!("Pause all players: {players:?}");
+
+    let results = futures::future::join_all(players.into_iter().map(|player| {
+        let connection = state.connection.clone();
+        async move {
+            pause(State(connection), PlayerName(player.clone()))
+                .await
+                .inspect_err(|error| tracing::warn!("Failed to pause player: {player}: {error}"))
+                .map(|_| player)
+        }
+    }))
+    .await;
+    let paused = results.into_iter().flatten().collect::<Vec<_>>();
+
+    let mut resumable = state.resumable.lock().expect("resumable lock poisoned");
+    for player in &paused {
+        if !resumable.contains(player) {
+            resumable.push(player.clone());
+        }
+    }
+
+    Json(paused)
+}
+
+async fn get_resumable(State(state): State<MediaState>) -> Json<Vec<String>> {
+    Json(
+        state
+            .resumable
+            .lock()
+            .expect("resumable lock poisoned")
+            .clone(),
+    )
+}
+
+/// Resume players paused by pause all. Players failing to resume stay resumable to retry, unless
+/// they are gone from the bus.
+async fn resume(State(state): State<MediaState>) -> Json<Vec<String>> {
+    let players = std::mem::take(&mut *state.resumable.lock().expect("resumable lock poisoned"));
+    tracing::info!("Resume players: {players:?}");
+
+    let results = futures::future::join_all(players.into_iter().map(|player| {
+        let connection = state.connection.clone();
+        async move {
+            let result = play(State(connection), PlayerName(player.clone())).await;
+            (player, result)
+        }
+    }))
+    .await;
+
+    let mut resumed = Vec::new();
+    let mut resumable = state.resumable.lock().expect("resumable lock poisoned");
+    for (player, result) in results {
+        match result {
+            Ok(()) => resumed.push(player),
+            Err(error) => {
+                tracing::warn!("Failed to resume player: {player}: {error}");
+                if error.status_code() != StatusCode::NOT_FOUND && !resumable.contains(&player) {
+                    resumable.push(player);
+                }
+            }
+        }
+    }
+
+    Json(resumed)
+}
+
+async fn play_pause(
+    State(connection): State<Arc<Connection>>,
+    PlayerName(player): PlayerName,
+) -> Result<(), ApiError> {
+    tracing::info!("PlayPause: {}", &player);
+    let con = connection.as_ref();
+
+    let proxy = MprisPlayerProxy::try_create(con, &*player)
+        .await
+        .map_err(ApiError::ConstructPlayer)?;
+    ensure_capability(proxy.can_pause().await, &player, "CanPause")?;
+
+    proxy.play_pause().await.map_err(|error| {
+        ApiError::PlayPause(
+            anyhow::Error::new(error).context(format!("PlayPause player: {player}")),
+        )
+    })?;
+
+    Ok(())
+}
+
+async fn stop(
+    State(connection): State<Arc<Connection>>,
+    PlayerName(player): PlayerName,
+) -> Result<(), ApiError> {
+    tracing::info!("Stop: {}", &player);
+    let con = connection.as_ref();
+
+    let proxy = MprisPlayerProxy::try_create(con, &*player)
+        .await
+        .map_err(ApiError::ConstructPlayer)?;
+    ensure_can_control(&proxy, &player).await?;
+
+    proxy.stop().await.map_err(|error| {
+        ApiError::Stop(anyhow::Error::new(error).context(format!("Stop player: {player}")))
+    })?;
+
+    Ok(())
+}
+
+async fn open_uri(
+    State(connection): State<Arc<Connection>>,
+    PlayerName(player): PlayerName,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<(), ApiError> {
+    let uri = params.get("uri").ok_or(ApiError::MissingUri)?;
+
+    tracing::info!("OpenUri: {player} uri: {uri}");
+    let con = connection.as_ref();
+
+    let proxy = MprisPlayerProxy::try_create(con, &*player)
+        .await
+        .map_err(ApiError::ConstructPlayer)?;
+    ensure_can_control(&proxy, &player).await?;
+
+    proxy.open_uri(uri).await.map_err(|error| {
+        ApiError::OpenUri(
+            anyhow::Error::new(error).context(format!("OpenUri player: {player} uri: {uri}")),
+        )
+    })?;
+
+    Ok(())
+}
+
+/// Seek by offset seconds with `SetPosition` to the current position plus the offset clamped to
+/// the track length. Falls back to `Seek` if the player has no track id or fails to set position.
+pub(super) async fn seek(
+    State(state): State<MediaState>,
+    PlayerName(player): PlayerName,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<(), ApiError> {
+    let offset = params
+        .get("offset")
+        .map(|offset| {
+            offset
+                .parse::<i64>()
+                .context("Failed to convert offset into i64")
+        })
+        .transpose()
+        .map_err(|_| ApiError::InvalidOffset)?
+        .ok_or(ApiError::MissingOffset)?;
+
+    tracing::info!("Seek: {player} with {offset}");
+    let con = state.connection.as_ref();
+    let proxy = MprisPlayerProxy::without_cache(con, &*player)
+        .await
+        .map_err(ApiError::ConstructPlayer)?;
+    ensure_capability(proxy.can_seek().await, &player, "CanSeek")?;
+
+    let metadata = proxy
+        .metadata()
+        .await
+        .map_err(anyhow::Error::new)
+        .and_then(|metadata| Ok(Metadata::try_from(metadata)?))
+        .map_err(|error| {
+            ApiError::Seek(
+                error.context(format!("Failed to get player: {player} Metadata for seek")),
+            )
+        })?;
+    let position = proxy.position().await.map_err(|error| {
+        ApiError::Seek(
+            anyhow::Error::new(error)
+                .context(format!("Failed to get player: {player} Position for seek")),
+        )
+    })?;
+
+    let unit = position_unit(&state, &proxy, &player, metadata.length).await?;
+    let offset = offset.saturating_mul(unit.per_second());
+
+    let length = if metadata.length > 0 {
+        metadata.length
+    } else {
+        i64::MAX
+    };
+    let target = position.saturating_add(offset).clamp(0, length);
+
+    let track_id = Some(metadata.track_id.as_str())
+        .filter(|track_id| !track_id.is_empty() && *track_id != tracklist::NO_TRACK)
+        .and_then(|track_id| OwnedObjectPath::try_from(track_id).ok());
+
+    if let Some(track_id) = track_id {
+        tracing::debug!("Set player: {player} position to: {target} {unit:?}");
+        match proxy.set_position(track_id, target).await {
+            Ok(()) => return Ok(()),
+            Err(error) => {
+                tracing::debug!(
+                    "Failed to set player: {player} position, fall back to Seek: {error}"
+                )
+            }
+        }
+    }
+
+    tracing::debug!("Seek player: {player} by: {offset} {unit:?}");
+    proxy.seek(target - position).await.map_err(|error| {
+        ApiError::Seek(anyhow::Error::new(error).context(format!(
+            "Failed to Seek player: {player} by {offset} {unit:?}"
+        )))
+    })
+}
+
+/// Unit the player reports position in, as detected by the player watcher or probed once per
+/// player by watching how fast the position advances while playing. Guessed from the track
+/// length while the unit is unknown, see [`PositionUnit::guess`].
+async fn position_unit(
+    state: &MediaState,
+    proxy: &MprisPlayerProxy<'_>,
+    player: &str,
+    length: i64,
+) -> Result<PositionUnit, ApiError> {
+    let units = &state.hub.position_units;
+    if let Some(unit) = units.get(player) {
+        return Ok(unit);
+    }
+    let guess = PositionUnit::guess(length);
+    if units.is_probed(player) {
+        return Ok(guess);
+    }
+
+    let status = proxy.playback_status().await.map_err(|error| {
+        ApiError::PlaybackStatus(
+            anyhow::Error::new(error)
+                .context(format!("Failed to get player: {player} PlaybackStatus")),
+        )
+    })?;
+    if status != "Playing" {
+        return Ok(guess);
+    }
+
+    let read_position = || async {
+        proxy.position().await.map_err(|error| {
+            ApiError::Position(
+                anyhow::Error::new(error)
+                    .context(format!("Failed to get player: {player} Position")),
+            )
+        })
+    };
+    let before = read_position().await?;
+    let started = Instant::now();
+    time::sleep(Duration::from_millis(150)).await;
+    let advanced = read_position().await? - before;
+
+    let unit = PositionUnit::detect(advanced, started.elapsed());
+    units.insert(player, unit);
+
+    Ok(unit.unwrap_or(guess))
+}
+
+async fn get_position(
+    State(connection): State<Arc<Connection>>,
+    PlayerName(player): PlayerName,
+) -> Result<String, ApiError> {
+    tracing::info!("Get current player: {player} position");
+    let con = connection.as_ref();
+
+    let proxy = MprisPlayerProxy::try_create(con, player.clone())
+        .await
+        .map_err(ApiError::ConstructPlayer)?;
+
+    let pos = proxy.position().await.map_err(|error| {
+        ApiError::Position(
+            anyhow::Error::new(error).context(format!("Failed to get player: {player} Position")),
+        )
+    })?;
+
+    Ok(pos.to_string())
+}
+
+/// Longest interval anchors can be resent in.
+const MAX_RESYNC_SECS: u64 = 3600;
+
+/// Stream position of the player. Besides `position` events, sends `paused` (with the status)
+/// and `resumed` on playback status changes, `track-changed` with the track id and length of the
+/// new track, `seeked` and `rate`. The stream lasts until the player disappears.
+///
+/// With `mode=anchor` only `anchor` events are sent, see [`stream_anchors`]. Anchors are resent
+/// every `resync` seconds, 1 to 3600, defaults to 5.
+async fn get_positon_sse(
+    State(state): State<MediaState>,
+    PlayerName(player): PlayerName,
+    Query(params): Query<HashMap<String, String>>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    tracing::info!("Get positon SSE for player: {player}");
+    let anchor_mode = match params.get("mode").map(String::as_str) {
+        None | Some("position") => false,
+        Some("anchor") => true,
+        Some(mode) => {
+            return Sse::new(SseEvent::Single(Some(
+                Event::default().event("error").data(format!(
+                    "invalid mode: {mode}, expected one of: position, anchor"
+                )),
+            )));
+        }
+    };
+    let resync = match params.get("resync").map(|resync| resync.parse::<u64>()) {
+        None => Duration::from_secs(5),
+        Some(Ok(resync)) if (1..=MAX_RESYNC_SECS).contains(&resync) => Duration::from_secs(resync),
+        Some(_) => {
+            return Sse::new(SseEvent::Single(Some(
+                Event::default().event("error").data(format!(
+                    "invalid resync, expected 1 to {MAX_RESYNC_SECS} seconds"
+                )),
+            )));
+        }
+    };
+
+    let mut subscription = match state.hub.subscribe(&player).await {
+        Ok(subscription) => subscription,
+        Err(error) => {
+            return Sse::new(SseEvent::Single(Some(
+                Event::default().event("error").data(error.to_string()),
+            )));
+        }
+    };
+
+    if anchor_mode {
+        let proxy = match MprisPlayerProxy::without_cache(&state.connection, &*player).await {
+            Ok(proxy) => proxy,
+            Err(error) => {
+                return Sse::new(SseEvent::Single(Some(
+                    Event::default().event("error").data(error.to_string()),
+                )));
+            }
+        };
+        let (tx, rx) = tokio::sync::mpsc::channel(30);
+        tokio::spawn(stream_anchors(subscription, proxy, tx, resync));
+
+        return Sse::new(SseEvent::Multi(Box::new(
+            ReceiverStream::new(rx).map(Ok::<Event, Infallible>),
+        )));
+    }
+
+    #[derive(serde::Serialize)]
+    struct TrackChanged<'a> {
+        track_id: &'a str,
+        length: i64,
+    }
+
+    let PlayerState {
+        metadata,
+        status,
+        position,
+        rate,
+        ..
+    } = subscription.state();
+
+    let mut keepalive_interval = time::interval(Duration::from_secs(20));
+    let (tx, rx) = tokio::sync::mpsc::channel(30);
+
+    tokio::spawn(async move {
+        let mut track_id = metadata.track_id;
+        let mut length = metadata.length;
+        let mut playing = status == "Playing";
+        // end of stream is told once per track
+        let mut eos_sent = false;
+
+        // rate lets clients account for faster or slower playback between position events
+        let mut initial = vec![
+            Event::default().event("rate").data(rate.to_string()),
+            Event::default()
+                .event("position")
+                .data(position.to_string()),
+        ];
+        if !playing {
+            initial.push(Event::default().event("paused").data(status));
+        }
+        for event in initial {
+            if tx.send(event).await.is_err() {
+                tracing::debug!("Broke pipe");
+                return;
+            }
+        }
+
+        loop {
+            let event = tokio::select! {
+                _ = keepalive_interval.tick() => Event::default().event("keepalive").comment(""),
+                _ = tx.closed() => {
+                    tracing::debug!("Broke pipe");
+                    break;
+                }
+                event = subscription.recv() => match event {
+                    Some(PlayerEvent::Position(pos)) => {
+                        if length > 0 && pos >= length {
+                            if eos_sent {
+                                continue;
+                            }
+                            tracing::debug!("last frame, {pos} >= {length}, send EOS");
+                            eos_sent = true;
+                            Event::default().event("position").data("EOS")
+                        } else {
+                            Event::default().event("position").data(pos.to_string())
+                        }
+                    }
+                    Some(PlayerEvent::Seeked(pos)) => {
+                        eos_sent = false;
+                        Event::default().event("seeked").data(pos.to_string())
+                    }
+                    Some(PlayerEvent::Rate(rate)) => Event::default().event("rate").data(rate.to_string()),
+                    Some(PlayerEvent::Status(status)) => {
+                        match (playing, status == "Playing") {
+                            (false, true) => {
+                                playing = true;
+                                Event::default().event("resumed").data(status)
+                            }
+                            (true, false) => {
+                                playing = false;
+                                Event::default().event("paused").data(status)
+                            }
+                            _ => continue,
+                        }
+                    }
+                    Some(PlayerEvent::Metadata(metadata)) => {
+                        if metadata.track_id == track_id && metadata.length == length {
+                            continue;
+                        }
+                        track_id = metadata.track_id;
+                        length = metadata.length;
+                        eos_sent = false;
+
+                        Event::default().event("track-changed").data(
+                            serde_json::to_string(&TrackChanged { track_id: &track_id, length })
+                                .expect("track change should serialize to JSON"),
+                        )
+                    }
+                    Some(PlayerEvent::Closed(error)) => {
+                        let _ = tx.send(Event::default().event("error").data(error)).await;
+                        break;
+                    }
+                    Some(_) => continue,
+                    None => break,
+                }
+            };
+
+            if tx.send(event).await.is_err() {
+                tracing::debug!("Broke pipe");
+                break;
+            }
+        }
+    });
+    let stream = Box::new(ReceiverStream::new(rx).map(Ok::<Event, Infallible>));
+
+    Sse::new(SseEvent::Multi(stream))
+}
+
+/// Position anchor clients interpolate the position from until the next anchor:
+/// `position + (now - timestamp) * rate` while the status is `Playing`.
+#[derive(serde::Serialize)]
+struct PositionAnchor {
+    position: i64,
+    rate: f64,
+    status: String,
+    /// Milliseconds since UNIX epoch when the position was read.
+    timestamp: u128,
+}
+
+/// Send position anchor on start, on status, rate and track changes, when the player seeks and
+/// every resync interval. Position is read from the player for each anchor.
+async fn stream_anchors(
+    mut subscription: Subscription,
+    proxy: WithoutCaching<MprisPlayerProxy<'static>>,
+    tx: Sender<Event>,
+    resync: Duration,
+) {
+    let mut keepalive_interval = time::interval(Duration::from_secs(20));
+    let mut resync_interval = time::interval(resync);
+    let mut track_id = subscription.state().metadata.track_id;
+
+    loop {
+        tokio::select! {
+            _ = keepalive_interval.tick() => {
+                if tx.send(Event::default().event("keepalive").comment("")).await.is_err() {
+                    tracing::debug!("Broke pipe, failed to send keepalive, reciver is not present");
+                    break;
+                }
+                continue;
+            }
+            _ = tx.closed() => {
+                tracing::debug!("Broke pipe, reciver is not present");
+                break;
+            }
+            _ = resync_interval.tick() => {}
+            event = subscription.recv() => match event {
+                Some(PlayerEvent::Status(_) | PlayerEvent::Rate(_) | PlayerEvent::Seeked(_)) => {}
+                Some(PlayerEvent::Metadata(metadata)) if metadata.track_id != track_id => {
+                    track_id = metadata.track_id;
+                }
+                Some(PlayerEvent::Closed(error)) => {
+                    let _ = tx.send(Event::default().event("error").data(error)).await;
+                    break;
+                }
+                Some(_) => continue,
+                None => break,
+            }
+        }
+
+        let state = subscription.state();
+        let position = proxy.position().await.unwrap_or_else(|error| {
+            tracing::debug!("Failed to read position for anchor, using last known: {error}");
+            state.position
+        });
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let anchor = PositionAnchor {
+            position,
+            rate: state.rate,
+            status: state.status,
+            timestamp,
+        };
+
+        let event = Event::default().event("anchor").data(
+            serde_json::to_string(&anchor).expect("position anchor should serialize to JSON"),
+        );
+        if tx.send(event).await.is_err() {
+            tracing::debug!("Broke pipe, reciver is not present");
+            break;
+        }
+        // anchor was just sent, next resync is due one interval from now
+        resync_interval.reset();
+    }
+}
+
+async fn set_position(
+    State(connection): State<Arc<Connection>>,
+    PlayerName(player): PlayerName,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<(), ApiError> {
+    let track_id = params.get("track_id").ok_or(ApiError::MissingTrackId)?;
+    let position = params.get("position").ok_or(ApiError::MissingPosition)?;
+
+    let position = position
+        .parse::<i64>()
+        .context("Failed to parse position as i64")
+        .map_err(|_| ApiError::InvalidPosition)?;
+
+    tracing::info!("SetPosition: {player} position: {position}, track_id: {track_id}");
+    let con = connection.as_ref();
+
+    let proxy = MprisPlayerProxy::try_create(con, &*player)
+        .await
+        .map_err(ApiError::ConstructPlayer)?;
+    ensure_capability(proxy.can_seek().await, &player, "CanSeek")?;
+
+    let track_id = OwnedObjectPath::try_from(track_id.as_str()).map_err(|error| {
+        ApiError::SetPosition(anyhow::Error::new(error).context(format!(
+            "Failed to create ObjectPath from track_id '{track_id}'"
+        )))
+    })?;
+
+    proxy
+        .set_position(track_id, position)
+        .await
+        .map_err(|error| {
+            ApiError::SetPosition(
+                anyhow::Error::new(error)
+                    .context(format!("Failed to set player: {player} position")),
+            )
+        })?;
+
+    Ok(())
+}
+
+async fn get_playback_status(
+    State(connection): State<Arc<Connection>>,
+    PlayerName(player): PlayerName,
+) -> Result<String, ApiError> {
+    tracing::info!("Get current playback status for player: {player}");
+    let con = connection.as_ref();
+
+    let proxy = MprisPlayerProxy::try_create(con, &*player)
+        .await
+        .map_err(ApiError::ConstructPlayer)?;
+
+    let status = proxy.playback_status().await.map_err(|error| {
+        ApiError::PlaybackStatus(
+            anyhow::Error::new(error)
+                .context(format!("Failed to get player: {player} PlaybackStatus")),
+        )
+    })?;
+
+    Ok(status.to_string())
+}
+
+async fn get_capabilities(
+    State(connection): State<Arc<Connection>>,
+    PlayerName(player): PlayerName,
+) -> Result<Json<Capabilities>, ApiError> {
+    tracing::info!("Get player: {player} capabilities");
+    let con = connection.as_ref();
+
+    let proxy = MprisPlayerProxy::without_cache(con, &*player)
+        .await
+        .map_err(ApiError::ConstructPlayer)?;
+
+    Ok(Json(Capabilities::read(&proxy).await))
+}
+
+/// Check the player advertises the capability before acting on it. Failing to read the
+/// capability is not an error, the action itself will tell whether it is supported.
+fn ensure_capability(
+    capability: zbus::Result<bool>,
+    player: &str,
+    name: &str,
+) -> Result<(), ApiError> {
+    match capability {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(ApiError::Unsupported(format!(
+            "Player: {player} does not allow the action, {name} is false"
+        ))),
+        Err(error) => {
+            tracing::debug!("Failed to get player: {player} {name}, assuming true: {error}");
+            Ok(())
+        }
+    }
+}
+
+async fn ensure_can_control(proxy: &MprisPlayerProxy<'_>, player: &str) -> Result<(), ApiError> {
+    ensure_capability(proxy.can_control().await, player, "CanControl")
+}
+
+async fn get_loop_status(
+    State(connection): State<Arc<Connection>>,
+    PlayerName(player): PlayerName,
+) -> Result<String, ApiError> {
+    tracing::info!("Get player: {player} loop status");
+    let con = connection.as_ref();
+
+    let proxy = MprisPlayerProxy::without_cache(con, &*player)
+        .await
+        .map_err(ApiError::ConstructPlayer)?;
+
+    proxy.loop_status().await.map_err(|error| {
+        if player::is_unsupported(&error) {
+            ApiError::Unsupported(format!("Player: {player} does not support LoopStatus"))
+        } else {
+            ApiError::LoopStatus(
+                anyhow::Error::new(error)
+                    .context(format!("Failed to get player: {player} LoopStatus")),
+            )
+        }
+    })
+}
+
+async fn set_loop_status(
+    State(connection): State<Arc<Connection>>,
+    PlayerName(player): PlayerName,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<(), ApiError> {
+    let loop_status = params
+        .get("loop_status")
+        .ok_or(ApiError::MissingLoopStatus)?;
+    if !player::LOOP_STATUSES.contains(&loop_status.as_str()) {
+        return Err(ApiError::InvalidLoopStatus);
+    }
+
+    tracing::info!("Set player: {player} loop status: {loop_status}");
+    let con = connection.as_ref();
+
+    let proxy = MprisPlayerProxy::try_create(con, &*player)
+        .await
+        .map_err(ApiError::ConstructPlayer)?;
+    ensure_can_control(&proxy, &player).await?;
+
+    proxy.set_loop_status(loop_status).await.map_err(|error| {
+        if player::is_unsupported(&error) {
+            ApiError::Unsupported(format!(
+                "Player: {player} does not support setting LoopStatus"
+            ))
+        } else {
+            ApiError::LoopStatus(
+                anyhow::Error::new(error)
+                    .context(format!("Failed to set player: {player} LoopStatus")),
+            )
+        }
+    })
+}
+
+async fn get_shuffle(
+    State(connection): State<Arc<Connection>>,
+    PlayerName(player): PlayerName,
+) -> Result<String, ApiError> {
+    tracing::info!("Get player: {player} shuffle");
+    let con = connection.as_ref();
+
+    let proxy = MprisPlayerProxy::without_cache(con, &*player)
+        .await
+        .map_err(ApiError::ConstructPlayer)?;
+
+    let shuffle = proxy.shuffle().await.map_err(|error| {
+        if player::is_unsupported(&error) {
+            ApiError::Unsupported(format!("Player: {player} does not support Shuffle"))
+        } else {
+            ApiError::Shuffle(
+                anyhow::Error::new(error)
+                    .context(format!("Failed to get player: {player} Shuffle")),
+            )
+        }
+    })?;
+
+    Ok(shuffle.to_string())
+}
+
+async fn set_shuffle(
+    State(connection): State<Arc<Connection>>,
+    PlayerName(player): PlayerName,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<(), ApiError> {
+    let shuffle = params
+        .get("shuffle")
+        .ok_or(ApiError::MissingShuffle)?
+        .parse::<bool>()
+        .map_err(|_| ApiError::InvalidShuffle)?;
+
+    tracing::info!("Set player: {player} shuffle: {shuffle}");
+    let con = connection.as_ref();
+
+    let proxy = MprisPlayerProxy::try_create(con, &*player)
+        .await
+        .map_err(ApiError::ConstructPlayer)?;
+    ensure_can_control(&proxy, &player).await?;
+
+    proxy.set_shuffle(shuffle).await.map_err(|error| {
+        if player::is_unsupported(&error) {
+            ApiError::Unsupported(format!("Player: {player} does not support setting Shuffle"))
+        } else {
+            ApiError::Shuffle(
+                anyhow::Error::new(error)
+                    .context(format!("Failed to set player: {player} Shuffle")),
+            )
+        }
+    })
+}
+
+async fn get_rate(
+    State(connection): State<Arc<Connection>>,
+    PlayerName(player): PlayerName,
+) -> Result<String, ApiError> {
+    tracing::info!("Get player: {player} rate");
+    let con = connection.as_ref();
+
+    let proxy = MprisPlayerProxy::without_cache(con, &*player)
+        .await
+        .map_err(ApiError::ConstructPlayer)?;
+
+    let rate = proxy.rate().await.map_err(|error| {
+        if player::is_unsupported(&error) {
+            ApiError::Unsupported(format!("Player: {player} does not support Rate"))
+        } else {
+            ApiError::Rate(
+                anyhow::Error::new(error).context(format!("Failed to get player: {player} Rate")),
+            )
+        }
+    })?;
+
+    Ok(rate.to_string())
+}
+
+/// Set player rate clamped to the range the player advertises, responds with the applied rate.
+async fn set_rate(
+    State(connection): State<Arc<Connection>>,
+    PlayerName(player): PlayerName,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<String, ApiError> {
+    let rate = params
+        .get("rate")
+        .ok_or(ApiError::MissingRate)?
+        .parse::<f64>()
+        .ok()
+        .filter(|rate| rate.is_finite() && *rate > 0.0)
+        .ok_or(ApiError::InvalidRate)?;
+
+    tracing::info!("Set player: {player} rate: {rate}");
+    let con = connection.as_ref();
+
+    let proxy = MprisPlayerProxy::without_cache(con, &*player)
+        .await
+        .map_err(ApiError::ConstructPlayer)?;
+    ensure_can_control(&proxy, &player).await?;
+
+    // players not supporting rate are bound to 1.0 by the spec
+    // non-finite bounds of misbehaving players are ignored
+    let minimum = match proxy.minimum_rate().await {
+        Ok(minimum) if minimum.is_finite() => minimum,
+        Ok(_) => f64::NEG_INFINITY,
+        Err(_) => 1.0,
+    };
+    let maximum = match proxy.maximum_rate().await {
+        Ok(maximum) if maximum.is_finite() => maximum,
+        Ok(_) => f64::INFINITY,
+        Err(_) => 1.0,
+    };
+    let rate = rate.clamp(minimum, maximum.max(minimum));
+
+    tracing::debug!("Set player: {player} rate clamped to: {rate}, range: {minimum}..={maximum}");
+    proxy.set_rate(rate).await.map_err(|error| {
+        if player::is_unsupported(&error) {
+            ApiError::Unsupported(format!("Player: {player} does not support setting Rate"))
+        } else {
+            ApiError::Rate(
+                anyhow::Error::new(error).context(format!("Failed to set player: {player} Rate")),
+            )
+        }
+    })?;
+
+    Ok(rate.to_string())
+}
+
+async fn get_player_volume(
+    State(connection): State<Arc<Connection>>,
+    PlayerName(player): PlayerName,
+) -> Result<String, ApiError> {
+    tracing::info!("Get player: {player} volume");
+    let con = connection.as_ref();
+
+    let proxy = MprisPlayerProxy::without_cache(con, &*player)
+        .await
+        .map_err(ApiError::ConstructPlayer)?;
+
+    let volume = proxy.volume().await.map_err(|error| {
+        ApiError::PlayerVolume(
+            anyhow::Error::new(error).context(format!("Failed to get player: {player} Volume")),
+        )
+    })?;
+
+    Ok(player::volume_to_percent(volume).to_string())
+}
+
+pub(super) async fn set_player_volume(
+    State(connection): State<Arc<Connection>>,
+    PlayerName(player): PlayerName,
+    Form(volume): Form<VolumeForm>,
+) -> Result<(), ApiError> {
+    let volume = volume.percent;
+    if volume > 100 {
+        return Err(ApiError::InvalidVolume);
+    }
+
+    tracing::info!("Set player: {player} volume to percent: {volume}");
+    let con = connection.as_ref();
+
+    let proxy = MprisPlayerProxy::try_create(con, &*player)
+        .await
+        .map_err(ApiError::ConstructPlayer)?;
+    ensure_can_control(&proxy, &player).await?;
+
+    proxy
+        .set_volume(f64::from(volume) / 100.0)
+        .await
+        .map_err(|error| {
+            ApiError::PlayerVolume(
+                anyhow::Error::new(error).context(format!("Failed to set player: {player} Volume")),
+            )
+        })?;
+
+    Ok(())
+}
+
+async fn get_info(
+    State(connection): State<Arc<Connection>>,
+    PlayerName(player): PlayerName,
+) -> Result<Json<MediaPlayerInfo>, ApiError> {
+    tracing::info!("Get player: {player} info");
+    let con = connection.as_ref();
+
+    let proxy = MprisMediaPlayerProxy::try_create(con, &*player)
+        .await
+        .map_err(ApiError::ConstructPlayer)?;
+
+    let info = MediaPlayerInfo::read(&proxy).await.map_err(|error| {
+        ApiError::MediaPlayer(anyhow::Error::new(error).context(format!(
+            "Failed to get player: {player} MediaPlayer2 properties"
+        )))
+    })?;
+
+    Ok(Json(info))
+}
+
+async fn raise(
+    State(connection): State<Arc<Connection>>,
+    PlayerName(player): PlayerName,
+) -> Result<(), ApiError> {
+    tracing::info!("Raise: {player}");
+    let con = connection.as_ref();
+
+    let proxy = MprisMediaPlayerProxy::try_create(con, &*player)
+        .await
+        .map_err(ApiError::ConstructPlayer)?;
+    ensure_capability(proxy.can_raise().await, &player, "CanRaise")?;
+
+    proxy.raise().await.map_err(|error| {
+        ApiError::Raise(anyhow::Error::new(error).context(format!("Raise player: {player}")))
+    })
+}
+
+async fn quit(
+    State(connection): State<Arc<Connection>>,
+    PlayerName(player): PlayerName,
+) -> Result<(), ApiError> {
+    tracing::info!("Quit: {player}");
+    let con = connection.as_ref();
+
+    let proxy = MprisMediaPlayerProxy::try_create(con, &*player)
+        .await
+        .map_err(ApiError::ConstructPlayer)?;
+    ensure_capability(proxy.can_quit().await, &player, "CanQuit")?;
+
+    proxy.quit().await.map_err(|error| {
+        ApiError::Quit(anyhow::Error::new(error).context(format!("Quit player: {player}")))
+    })
 }
 
-async fn get_players(
+async fn get_fullscreen(
     State(connection): State<Arc<Connection>>,
-) -> Result<Json<Vec<(String, String)>>, ApiError> {
-    let players = super::get_players(&connection)
+    PlayerName(player): PlayerName,
+) -> Result<String, ApiError> {
+    tracing::info!("Get player: {player} fullscreen");
+    let con = connection.as_ref();
+
+    let proxy = MprisMediaPlayerProxy::try_create(con, &*player)
         .await
-        .map_err(ApiError::ListConnections)?
-        .collect::<Vec<String>>();
+        .map_err(ApiError::ConstructPlayer)?;
 
-    let identities = stream! {
-        for p in players {
-            yield (super::get_identity(&connection, &p).await, p)
+    let fullscreen = proxy.fullscreen().await.map_err(|error| {
+        if player::is_unsupported(&error) {
+            ApiError::Unsupported(format!("Player: {player} does not support Fullscreen"))
+        } else {
+            ApiError::Fullscreen(
+                anyhow::Error::new(error)
+                    .context(format!("Failed to get player: {player} Fullscreen")),
+            )
         }
-    };
-
-    let player_identities = identities
-        .then(|(result, player)| async {
-            match result {
-                Ok(identity) => Some((identity, player)),
-                Err(error) => {
-                    tracing::warn!(
-                        "Error fetching identity for player: {player}, skipping player, {error}"
-                    );
-                    None
-                }
-            }
-        })
-        .filter(|player_identity| future::ready(player_identity.is_some()))
-        .flat_map(|v| {
-            futures::stream::once(async { v.expect("player identity is never None here") })
-        })
-        .collect::<Vec<(String, String)>>()
-        .await;
+    })?;
 
-    Ok(Json(player_identities))
+    Ok(fullscreen.to_string())
 }
 
-// for a reference, UI cannot handle streams as of now
-async fn get_players_stream(
+/// Set player fullscreen to given `fullscreen` query parameter or toggle it if not given.
+/// Responds with the new fullscreen state.
+async fn set_fullscreen(
     State(connection): State<Arc<Connection>>,
-) -> Result<Response, ApiError> {
-    let players = super::get_players(&connection)
+    PlayerName(player): PlayerName,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<String, ApiError> {
+    let fullscreen = params
+        .get("fullscreen")
+        .map(|fullscreen| fullscreen.parse::<bool>())
+        .transpose()
+        .map_err(|_| ApiError::InvalidFullscreen)?;
+
+    let con = connection.as_ref();
+    let proxy = MprisMediaPlayerProxy::try_create(con, &*player)
         .await
-        .map_err(ApiError::ListConnections)?
-        .collect::<Vec<String>>();
+        .map_err(ApiError::ConstructPlayer)?;
+    ensure_capability(
+        proxy.can_set_fullscreen().await,
+        &player,
+        "CanSetFullscreen",
+    )?;
+
+    let fullscreen = match fullscreen {
+        Some(fullscreen) => fullscreen,
+        None => !proxy.fullscreen().await.map_err(|error| {
+            ApiError::Fullscreen(
+                anyhow::Error::new(error)
+                    .context(format!("Failed to get player: {player} Fullscreen")),
+            )
+        })?,
+    };
 
-    let identities = stream! {
-        for p in players {
-            yield (super::get_identity(&connection, &p).await, p)
+    tracing::info!("Set player: {player} fullscreen: {fullscreen}");
+    proxy.set_fullscreen(fullscreen).await.map_err(|error| {
+        if player::is_unsupported(&error) {
+            ApiError::Unsupported(format!(
+                "Player: {player} does not support setting Fullscreen"
+            ))
+        } else {
+            ApiError::Fullscreen(
+                anyhow::Error::new(error)
+                    .context(format!("Failed to set player: {player} Fullscreen")),
+            )
         }
-    };
+    })?;
 
-    let player_identities = identities
-        .then(|(result, player)| async {
-            match result {
-                Ok(identity) => Some((identity, player)),
-                Err(error) => {
-                    tracing::warn!(
-                        "Error fetching identity for player: {player}, skipping player, {error}"
-                    );
-                    None
-                }
-            }
-        })
-        .filter(|player_identity| future::ready(player_identity.is_some()))
-        .map(|v| {
-            let mut json = serde_json::to_string(&v.expect("player identity is never None here"))
-                .expect("Should be JSON serializable anyways");
-            json.push('\n');
-            json
-        })
-        .boxed()
-        .map(Ok::<String, Infallible>);
+    Ok(fullscreen.to_string())
+}
 
-    let response = Response::builder()
-        .header("content-type", "application/json+stream")
-        .status(StatusCode::OK)
-        .body(Body::from_stream(player_identities))
-        .map_err(|error| {
-            ApiError::Players(anyhow::anyhow!("Error creating players body: {error}"))
-        })?;
+fn track_id_param(params: &HashMap<String, String>) -> Result<ObjectPath<'_>, ApiError> {
+    let track_id = params.get("track_id").ok_or(ApiError::MissingTrackId)?;
 
-    Ok(response)
+    ObjectPath::try_from(track_id.as_str()).map_err(|_| ApiError::InvalidTrackId)
 }
 
-async fn get_metadata(
+async fn get_tracklist(
     State(connection): State<Arc<Connection>>,
-    Path(player): Path<String>,
-) -> Result<Json<Metadata>, ApiError> {
-    tracing::info!("Get player metadata: {}", &player);
+    PlayerName(player): PlayerName,
+) -> Result<Json<Vec<Metadata>>, ApiError> {
+    tracing::info!("Get player: {player} track list");
     let con = connection.as_ref();
 
-    let proxy = MprisPlayerProxy::try_create(con, &*player)
+    let proxy = MprisTrackListProxy::try_create(con, &*player)
         .await
         .map_err(ApiError::ConstructPlayer)?;
 
-    let meta = proxy.metadata().await.map_err(|error| {
-        ApiError::Metadata(anyhow::anyhow!("Failed to get player metadata: {error}"))
+    let tracks = proxy.tracks().await.map_err(|error| {
+        if player::is_unsupported(&error) {
+            ApiError::Unsupported(format!("Player: {player} does not support TrackList"))
+        } else {
+            ApiError::TrackList(
+                anyhow::Error::new(error).context(format!("Failed to get player: {player} Tracks")),
+            )
+        }
     })?;
+    let track_ids = tracks
+        .iter()
+        .map(|track| track.as_ref())
+        .collect::<Vec<_>>();
 
-    tracing::debug!(metadata = ?&meta, "Before from conversion");
-
-    let metadata: Metadata = meta.into();
+    let metadata = proxy
+        .get_tracks_metadata(&track_ids)
+        .await
+        .map_err(|error| {
+            ApiError::TrackList(
+                anyhow::Error::new(error)
+                    .context(format!("Failed to get player: {player} tracks metadata")),
+            )
+        })?;
 
-    tracing::debug!(metadata = ?&metadata);
+    let metadata = metadata.into_iter().map(Metadata::from).collect();
 
     Ok(Json(metadata))
 }
 
-async fn play_pause(
+async fn tracklist_goto(
     State(connection): State<Arc<Connection>>,
-    Path(player): Path<String>,
+    PlayerName(player): PlayerName,
+    Query(params): Query<HashMap<String, String>>,
 ) -> Result<(), ApiError> {
-    tracing::info!("PlayPause: {}", &player);
+    let track_id = track_id_param(&params)?;
+
+    tracing::info!("GoTo: {player} track_id: {track_id}");
     let con = connection.as_ref();
 
-    let proxy = MprisPlayerProxy::try_create(con, &*player)
+    let proxy = MprisTrackListProxy::try_create(con, &*player)
         .await
         .map_err(ApiError::ConstructPlayer)?;
 
-    proxy.play_pause().await.map_err(|error| {
-        ApiError::PlayPause(anyhow::anyhow!("PlayPause player: {player}: {error}"))
-    })?;
-
-    Ok(())
+    proxy.go_to(&track_id).await.map_err(|error| {
+        ApiError::TrackList(
+            anyhow::Error::new(error)
+                .context(format!("GoTo player: {player} track_id: {track_id}")),
+        )
+    })
 }
 
-// TODO consider implementing seek via set_position like get_positon() + offset
-// this way the seek is more reliable
-// needed track_id from client
-async fn seek(
+async fn tracklist_add(
     State(connection): State<Arc<Connection>>,
-    Path(player): Path<String>,
+    PlayerName(player): PlayerName,
     Query(params): Query<HashMap<String, String>>,
 ) -> Result<(), ApiError> {
-    let offset = params
-        .get("offset")
-        .map(|offset| {
-            offset
-                .parse::<i64>()
-                .context("Failed to convert offset into i64")
-        })
+    let uri = params.get("uri").ok_or(ApiError::MissingUri)?;
+    let after_track = params
+        .get("after_track")
+        .map(String::as_str)
+        .unwrap_or(tracklist::NO_TRACK);
+    let after_track = ObjectPath::try_from(after_track).map_err(|_| ApiError::InvalidTrackId)?;
+    let set_as_current = params
+        .get("set_as_current")
+        .map(|set_as_current| set_as_current.parse::<bool>())
         .transpose()
-        .map_err(|_| ApiError::InvalidOffset)?
-        .ok_or(ApiError::MissingOffset)?;
+        .map_err(|_| ApiError::InvalidSetAsCurrent)?
+        .unwrap_or(false);
 
-    tracing::info!("Seek: {player} with {offset}");
+    tracing::info!(
+        "AddTrack: {player} uri: {uri}, after_track: {after_track}, set_as_current: {set_as_current}"
+    );
     let con = connection.as_ref();
-    let proxy = MprisPlayerProxy::without_cache(con, &*player)
+
+    let proxy = MprisTrackListProxy::try_create(con, &*player)
         .await
         .map_err(ApiError::ConstructPlayer)?;
+    ensure_capability(proxy.can_edit_tracks().await, &player, "CanEditTracks")?;
 
-    async fn seek_offset(
-        proxy: &MprisPlayerProxy<'_>,
-        player: &str,
-        offset_micros: i64,
-    ) -> Result<(), ApiError> {
-        proxy.seek(offset_micros).await.map_err(|error| {
-            ApiError::Seek(anyhow::anyhow!(
-                "Failed to Seek player: {player} to {offset_micros} offest micros: {error}"
-            ))
+    proxy
+        .add_track(uri, &after_track, set_as_current)
+        .await
+        .map_err(|error| {
+            ApiError::TrackList(
+                anyhow::Error::new(error).context(format!("AddTrack player: {player} uri: {uri}")),
+            )
         })
-    }
-
-    let position = proxy.position().await.map_err(|error| {
-        ApiError::Seek(anyhow::anyhow!(
-            "Fafiled to get player: {player} Metadata for position: {error}"
-        ))
-    })?;
-
-    let offset_micros = offset * 1000 * 1000;
-
-    tracing::debug!("Try seek as microseconds: {offset_micros}");
-    seek_offset(&proxy, &player, offset_micros).await?;
-
-    let (_tx, rx) = oneshot::channel::<i64>();
-    let _ = timeout(Duration::from_millis(100), rx).await;
-
-    let after_seek_position = proxy.position().await.map_err(|error| {
-        ApiError::Seek(anyhow::anyhow!(
-            "Failed to get player: {player} Metadata for position after seek: {error}"
-        ))
-    })?;
-    let length_seeked_secs = Duration::from_micros(position as u64)
-        .abs_diff(Duration::from_micros(after_seek_position as u64))
-        .as_secs();
-
-    tracing::debug!(
-        "After seeking, original: {position} after: {after_seek_position} diff in seconds: {length_seeked_secs}",
-    );
-
-    // check the seek length is actually 5 seconds otherwise try with milliseconds
-    if length_seeked_secs < 4 {
-        tracing::debug!("Incorrent offset as microseconds, trying with milliseconds");
-        // revert the offset
-        seek_offset(&proxy, &player, -offset_micros).await?;
-        // try seeking with millis instead
-        seek_offset(&proxy, &player, offset * 1000).await?;
-    }
-
-    Ok(())
 }
 
-async fn get_position(
+async fn tracklist_remove(
     State(connection): State<Arc<Connection>>,
-    Path(player): Path<String>,
-) -> Result<String, ApiError> {
-    tracing::info!("Get current player: {player} position");
+    PlayerName(player): PlayerName,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<(), ApiError> {
+    let track_id = track_id_param(&params)?;
+
+    tracing::info!("RemoveTrack: {player} track_id: {track_id}");
     let con = connection.as_ref();
 
-    let proxy = MprisPlayerProxy::try_create(con, player.clone())
+    let proxy = MprisTrackListProxy::try_create(con, &*player)
         .await
         .map_err(ApiError::ConstructPlayer)?;
+    ensure_capability(proxy.can_edit_tracks().await, &player, "CanEditTracks")?;
 
-    let pos = proxy.position().await.map_err(|error| {
-        ApiError::Position(anyhow::anyhow!(
-            "Failed to get player: {player} Position: {error}"
-        ))
-    })?;
-
-    Ok(pos.to_string())
+    proxy.remove_track(&track_id).await.map_err(|error| {
+        ApiError::TrackList(
+            anyhow::Error::new(error)
+                .context(format!("RemoveTrack player: {player} track_id: {track_id}")),
+        )
+    })
 }
 
-async fn get_positon_sse(
+async fn get_tracklist_sse(
     State(connection): State<Arc<Connection>>,
-    Path(player): Path<String>,
+    PlayerName(player): PlayerName,
 ) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
-    tracing::info!("Get positon SSE for player: {player}");
+    tracing::info!("Get track list SSE for player: {player}");
     let con = connection.as_ref();
-    let proxy = match MprisPlayerProxy::without_cache(con, &*player).await {
+
+    let proxy = match MprisTrackListProxy::try_create(con, &*player).await {
         Ok(proxy) => proxy,
         Err(error) => {
             return Sse::new(SseEvent::Single(Some(
@@ -286,14 +1635,14 @@ async fn get_positon_sse(
         }
     };
 
-    let length = match proxy
-        .get_length()
-        .map_err(|error| {
-            anyhow::anyhow!("Failed to get player: {player} Metadata for position: {error}")
-        })
-        .await
-    {
-        Ok(length) => length,
+    let streams = futures::try_join!(
+        proxy.receive_track_list_replaced(),
+        proxy.receive_track_added(),
+        proxy.receive_track_removed(),
+        proxy.receive_track_metadata_changed(),
+    );
+    let (mut replaced, mut added, mut removed, mut metadata_changed) = match streams {
+        Ok(streams) => streams,
         Err(error) => {
             return Sse::new(SseEvent::Single(Some(
                 Event::default().event("error").data(error.to_string()),
@@ -301,121 +1650,170 @@ async fn get_positon_sse(
         }
     };
 
-    let mut interval = time::interval(Duration::from_millis(100));
+    fn json_event(event_type: &str, data: &impl serde::Serialize) -> Event {
+        Event::default().event(event_type).data(
+            serde_json::to_string_pretty(data).expect("track list event should serialize to JSON"),
+        )
+    }
+
+    let mut keepalive_interval = time::interval(Duration::from_secs(20));
     let (tx, rx) = tokio::sync::mpsc::channel(30);
 
     tokio::spawn(async move {
         loop {
-            let _ = interval.tick().await;
+            let event = tokio::select! {
+                _ = keepalive_interval.tick() => Ok(Event::default().event("keepalive").comment("")),
+                _ = tx.closed() => break,
+                Some(signal) = replaced.next() => signal.args().map_err(anyhow::Error::new).map(|args| json_event("track-list-replaced", &TrackListReplacedEvent {
+                    tracks: args.tracks.iter().map(ToString::to_string).collect(),
+                    current_track: args.current_track.to_string(),
+                })),
+                Some(signal) = added.next() => signal.args().map_err(anyhow::Error::new).map(|args| json_event("track-added", &TrackAddedEvent {
+                    metadata: Metadata::from(args.metadata),
+                    after_track: args.after_track.to_string(),
+                })),
+                Some(signal) = removed.next() => signal.args().map_err(anyhow::Error::new).map(|args| Event::default().event("track-removed").data(args.track_id.as_str())),
+                Some(signal) = metadata_changed.next() => signal.args().map_err(anyhow::Error::new).map(|args| json_event("track-metadata-changed", &TrackMetadataChangedEvent {
+                    track_id: args.track_id.to_string(),
+                    metadata: Metadata::from(args.metadata),
+                })),
+            };
 
-            tracing::debug!("Check the postion: {player}, length: {length}");
-            let pos = match proxy.position().await {
-                Ok(position) => position,
+            let event = match event {
+                Ok(event) => event,
                 Err(error) => {
-                    let _ = tx
-                        .send(Event::default().event("error").data(
-                            anyhow::anyhow!("Failed to get player position: {error}").to_string(),
-                        ))
-                        .await;
-                    return;
+                    tracing::warn!("Failed to read track list signal of player: {player}: {error}");
+                    continue;
                 }
             };
 
-            if pos >= length {
-                tracing::debug!("last frame, {pos} >= {length}, send EOS");
-                let _ = tx
-                    .send(Event::default().event("position").data("EOS"))
-                    .await;
+            if tx.send(event).await.is_err() {
+                tracing::debug!("Broke pipe, reciver is not present");
                 break;
-            } else {
-                tracing::debug!("Still streaming for postion: {pos}");
-
-                let status = match proxy.playback_status().await {
-                    Ok(status) => status,
-                    Err(error) => {
-                        tracing::warn!("Failed to get player playback status: error: {error:#?}");
-                        String::new()
-                    }
-                };
-                if status != "Playing" {
-                    tracing::error!(
-                        "Player is not playing, closing stream, stream may have stalled or closed abruptly"
-                    );
-                    break;
-                }
-                if tx
-                    .send(Event::default().event("position").data(format!("{pos}")))
-                    .await
-                    .is_err()
-                {
-                    tracing::debug!("Broke pipe");
-
-                    break;
-                }
             }
         }
     });
-    let stream = Box::new(ReceiverStream::new(rx).map(Ok::<Event, Infallible>));
 
-    Sse::new(SseEvent::Multi(stream))
+    let stream = ReceiverStream::new(rx).map(Ok::<Event, Infallible>);
+
+    Sse::new(SseEvent::Multi(Box::new(stream)))
 }
 
-async fn set_position(
+/// List player playlists, paged with `index` and `max_count` and ordered with `order` and
+/// `reverse_order` query parameters.
+async fn get_playlists(
     State(connection): State<Arc<Connection>>,
-    Path(player): Path<String>,
+    PlayerName(player): PlayerName,
     Query(params): Query<HashMap<String, String>>,
-) -> Result<(), ApiError> {
-    let track_id = params.get("track_id").ok_or(ApiError::MissingTrackId)?;
-    let position = params.get("position").ok_or(ApiError::MissingPosition)?;
-
-    let position = position
-        .parse::<i64>()
-        .context("Failed to parse position as i64")
-        .map_err(|_| ApiError::InvalidPosition)?;
+) -> Result<Json<Playlists>, ApiError> {
+    let index = params
+        .get("index")
+        .map(|index| index.parse::<u32>())
+        .transpose()
+        .map_err(|_| ApiError::InvalidIndex)?
+        .unwrap_or(0);
+    let max_count = params
+        .get("max_count")
+        .map(|max_count| max_count.parse::<u32>())
+        .transpose()
+        .map_err(|_| ApiError::InvalidMaxCount)?
+        .unwrap_or(100);
+    let order = params
+        .get("order")
+        .map(|order| {
+            playlists::ORDERINGS
+                .contains(&order.as_str())
+                .then_some(order.as_str())
+                .ok_or(ApiError::InvalidOrder)
+        })
+        .transpose()?;
+    let reverse_order = params
+        .get("reverse_order")
+        .map(|reverse_order| reverse_order.parse::<bool>())
+        .transpose()
+        .map_err(|_| ApiError::InvalidReverseOrder)?
+        .unwrap_or(false);
 
-    tracing::info!("SetPosition: {player} position: {position}, track_id: {track_id}");
+    tracing::info!("Get player: {player} playlists, index: {index}, max_count: {max_count}");
     let con = connection.as_ref();
 
-    let proxy = MprisPlayerProxy::try_create(con, &*player)
+    let proxy = MprisPlaylistsProxy::try_create(con, &*player)
         .await
         .map_err(ApiError::ConstructPlayer)?;
 
-    let track_id = OwnedObjectPath::try_from(track_id.as_str()).map_err(|error| {
-        ApiError::SetPosition(anyhow::anyhow!(
-            "Failed to create ObjectPath from track_id '{track_id}': {error}"
-        ))
+    let orderings = proxy.orderings().await.map_err(|error| {
+        if player::is_unsupported(&error) {
+            ApiError::Unsupported(format!("Player: {player} does not support Playlists"))
+        } else {
+            ApiError::Playlists(
+                anyhow::Error::new(error)
+                    .context(format!("Failed to get player: {player} Orderings")),
+            )
+        }
     })?;
+    // default to the first ordering the player offers
+    let order = order
+        .or_else(|| orderings.first().map(String::as_str))
+        .unwrap_or(playlists::ORDERINGS[0]);
 
-    proxy
-        .set_position(track_id, position)
+    let found = proxy
+        .get_playlists(index, max_count, order, reverse_order)
         .await
         .map_err(|error| {
-            ApiError::SetPosition(anyhow::anyhow!(
-                "Failed to set player: {player} position: {error}"
-            ))
+            ApiError::Playlists(
+                anyhow::Error::new(error)
+                    .context(format!("Failed to get player: {player} playlists")),
+            )
         })?;
+    let playlist_count = proxy.playlist_count().await.map_err(|error| {
+        ApiError::Playlists(
+            anyhow::Error::new(error)
+                .context(format!("Failed to get player: {player} PlaylistCount")),
+        )
+    })?;
+    let active_playlist = match proxy.active_playlist().await {
+        Ok((true, playlist)) => Some(Playlist::from(playlist)),
+        Ok((false, _)) => None,
+        Err(error) => {
+            tracing::warn!("Failed to get player: {player} ActivePlaylist: {error}");
+            None
+        }
+    };
 
-    Ok(())
+    Ok(Json(Playlists {
+        playlists: found.into_iter().map(Playlist::from).collect(),
+        active_playlist,
+        playlist_count,
+        orderings,
+    }))
 }
 
-async fn get_playback_status(
+async fn activate_playlist(
     State(connection): State<Arc<Connection>>,
-    Path(player): Path<String>,
-) -> Result<String, ApiError> {
-    tracing::info!("Get current playback status for player: {player}");
+    PlayerName(player): PlayerName,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<(), ApiError> {
+    let playlist_id = params
+        .get("playlist_id")
+        .ok_or(ApiError::MissingPlaylistId)?;
+    let playlist_id =
+        ObjectPath::try_from(playlist_id.as_str()).map_err(|_| ApiError::InvalidPlaylistId)?;
+
+    tracing::info!("ActivatePlaylist: {player} playlist_id: {playlist_id}");
     let con = connection.as_ref();
 
-    let proxy = MprisPlayerProxy::try_create(con, &*player)
+    let proxy = MprisPlaylistsProxy::try_create(con, &*player)
         .await
         .map_err(ApiError::ConstructPlayer)?;
 
-    let status = proxy.playback_status().await.map_err(|error| {
-        ApiError::PlaybackStatus(anyhow::anyhow!(
-            "Failed to get player: {player} PlaybackStatus: {error}"
-        ))
-    })?;
-
-    Ok(status.to_string())
+    proxy
+        .activate_playlist(&playlist_id)
+        .await
+        .map_err(|error| {
+            ApiError::Playlists(anyhow::Error::new(error).context(format!(
+                "ActivatePlaylist player: {player} playlist_id: {playlist_id}"
+            )))
+        })
 }
 
 async fn get_image(Path(url): Path<String>) -> Result<Vec<u8>, ApiError> {
@@ -438,6 +1836,13 @@ enum PlayerSseEvent {
     Metadata,
     Status,
     Volume,
+    PlayerVolume,
+    LoopStatus,
+    Shuffle,
+    Rate,
+    Capabilities,
+    Seeked,
+    State,
 }
 
 impl Display for PlayerSseEvent {
@@ -446,52 +1851,25 @@ impl Display for PlayerSseEvent {
             Self::Metadata => "metadata",
             Self::Status => "status",
             Self::Volume => "volume",
+            Self::PlayerVolume => "player-volume",
+            Self::LoopStatus => "loop-status",
+            Self::Shuffle => "shuffle",
+            Self::Rate => "rate",
+            Self::Capabilities => "capabilities",
+            Self::State => "state",
+            Self::Seeked => "seeked",
         };
         write!(f, "{name}")
     }
 }
 
 async fn get_player_sse(
-    State(connection): State<Arc<Connection>>,
-    Path(player): Path<String>,
+    State(state): State<MediaState>,
+    PlayerName(player): PlayerName,
 ) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
     tracing::info!("Get SSE for player: {player}");
-    let con = connection.as_ref();
-    let proxy = match MprisPlayerProxy::without_cache(con, &*player).await {
-        Ok(proxy) => proxy,
-        Err(error) => {
-            return Sse::new(SseEvent::Single(Some(
-                Event::default().event("error").data(error.to_string()),
-            )));
-        }
-    };
-
-    async fn get_metadata(
-        proxy: &MprisPlayerProxy<'_>,
-        player: &str,
-    ) -> Result<Metadata, ApiError> {
-        proxy
-            .metadata()
-            .and_then(|metadata| async { Ok(Into::<Metadata>::into(metadata)) })
-            .map_err(|error| {
-                ApiError::Metadata(anyhow::anyhow!(
-                    "Failed to get player: {player} Metadata: {error}"
-                ))
-            })
-            .await
-    }
-
-    // initial metadata
-    let mut metadata = match get_metadata(&proxy, &player).await {
-        Ok(metadata) => metadata,
-        Err(error) => {
-            return Sse::new(SseEvent::Single(Some(
-                Event::default().event("error").data(error.to_string()),
-            )));
-        }
-    };
-    let mut status = match proxy.playback_status().await {
-        Ok(status) => status,
+    let mut subscription = match state.hub.subscribe(&player).await {
+        Ok(subscription) => subscription,
         Err(error) => {
             return Sse::new(SseEvent::Single(Some(
                 Event::default().event("error").data(error.to_string()),
@@ -499,22 +1877,6 @@ async fn get_player_sse(
         }
     };
 
-    let mut volume = match pulseaudio::get_volume().await {
-        Ok(volume) => volume,
-        Err(error) => {
-            tracing::warn!(
-                "Failed to get initial volume in player sse, using empty string: error: {error:#?}"
-            );
-            String::new()
-        }
-    };
-
-    async fn send_error(tx: &Sender<Event>, error: impl Error) {
-        let _ = tx
-            .send(Event::default().event("error").data(error.to_string()))
-            .await;
-    }
-
     async fn send_event<S: AsRef<str>>(
         tx: &Sender<Event>,
         event_type: PlayerSseEvent,
@@ -532,11 +1894,16 @@ async fn get_player_sse(
         true
     }
 
-    let mut interval = time::interval(Duration::from_millis(500));
     let mut keepalive_interval = time::interval(Duration::from_secs(20));
     let (tx, rx) = tokio::sync::mpsc::channel(30);
 
     tokio::spawn(async move {
+        let state = serde_json::to_string_pretty(&subscription.state())
+            .expect("player state should serialize to JSON");
+        if !send_event(&tx, PlayerSseEvent::State, state).await {
+            return;
+        }
+
         loop {
             tokio::select! {
                 _ = keepalive_interval.tick() => {
@@ -547,56 +1914,38 @@ async fn get_player_sse(
                         break;
                     }
                 }
-                _ = interval.tick() => {
-                    tracing::debug!("Check: {player} metadata for changes");
-                    let new_metadata = match get_metadata(&proxy, &player).await {
-                        Ok(position) => position,
-                        Err(error) => {
-                            send_error(&tx, error).await;
-                            return;
-                        }
-                    };
-
-                    if metadata != new_metadata {
-                        tracing::debug!("Player: {player} metadata changed: {new_metadata:?}");
-
-                        if !send_event(&tx, PlayerSseEvent::Metadata,
-                                serde_json::to_string_pretty(&new_metadata).expect("new metadata should serialize to JSON")
-                            ).await {
-                            break;
-                        }
-                        metadata = new_metadata;
-                    }
-
-                    let new_status = match proxy.playback_status().await {
-                        Ok(status) => status,
-                        Err(error) => {
-                            send_error(&tx, error).await;
-                            return;
+                _ = tx.closed() => {
+                    tracing::debug!("Broke pipe, reciver is not present");
+                    break;
+                }
+                event = subscription.recv() => {
+                    let sent = match event {
+                        Some(PlayerEvent::Metadata(metadata)) => {
+                            tracing::debug!("Player: {player} metadata changed: {metadata:?}");
+                            send_event(&tx, PlayerSseEvent::Metadata,
+                                serde_json::to_string_pretty(&metadata).expect("new metadata should serialize to JSON")
+                            ).await
                         }
-                    };
-
-                    if status != new_status {
-                        if !send_event(&tx, PlayerSseEvent::Status, &new_status).await {
+                        Some(PlayerEvent::Status(status)) => send_event(&tx, PlayerSseEvent::Status, &status).await,
+                        Some(PlayerEvent::Seeked(position)) => send_event(&tx, PlayerSseEvent::Seeked, position.to_string()).await,
+                        Some(PlayerEvent::Volume(volume)) => send_event(&tx, PlayerSseEvent::Volume, &volume).await,
+                        Some(PlayerEvent::LoopStatus(loop_status)) => send_event(&tx, PlayerSseEvent::LoopStatus, &loop_status).await,
+                        Some(PlayerEvent::Shuffle(shuffle)) => send_event(&tx, PlayerSseEvent::Shuffle, shuffle.to_string()).await,
+                        Some(PlayerEvent::Rate(rate)) => send_event(&tx, PlayerSseEvent::Rate, rate.to_string()).await,
+                        Some(PlayerEvent::Capabilities(capabilities)) => send_event(&tx, PlayerSseEvent::Capabilities,
+                            serde_json::to_string_pretty(&capabilities).expect("capabilities should serialize to JSON")
+                        ).await,
+                        Some(PlayerEvent::PlayerVolume(volume)) => send_event(&tx, PlayerSseEvent::PlayerVolume, volume.to_string()).await,
+                        Some(PlayerEvent::Position(_)) => true,
+                        Some(PlayerEvent::Closed(error)) => {
+                            let _ = tx.send(Event::default().event("error").data(error)).await;
                             break;
                         }
-                        status = new_status;
-                    }
-
-                    let new_volume = match pulseaudio::get_volume().await {
-                        Ok(volume) => volume,
-                        Err(error) => {
-                            tracing::warn!("Failed to get volume in player sse, using empty string, volume wont be sent as event to the client: error: {error:#?}");
-                            String::new()
-                        }
+                        None => break,
                     };
-                    if new_volume != volume {
-                        volume = new_volume;
 
-                        if !send_event(&tx, PlayerSseEvent::Volume, &volume).await {
-                            tracing::debug!("Broke pipe, failed to send volume");
-                            break;
-                        }
+                    if !sent {
+                        break;
                     }
                 }
             }
@@ -608,48 +1957,49 @@ async fn get_player_sse(
     Sse::new(SseEvent::Multi(Box::new(stream)))
 }
 
-// async fn next(
-//     State(connection): State<Arc<Connection>>,
-//     Path(player): Path<String>,
-// ) -> Result<(), ApiError> {
-//     tracing::info!("Call next on player: {player}");
-//
-//     let con = connection.as_ref();
-//
-//     let proxy = MprisPlayerProxy::new(con, player.clone())
-//         .await
-//         .with_context(|| {
-//             format!("Failed to create DBus Player2 connection MPRIS protocol for player: {player}")
-//         })
-//         .map_err(ApiError::PlaybackStatus)?;
-//
-//     proxy
-//         .next()
-//         .map_err(|error| ApiError::Next(anyhow::anyhow!("Failed to call next on player: {error}")))
-//         .await
-// }
-//
-// async fn previous(
-//     State(connection): State<Arc<Connection>>,
-//     Path(player): Path<String>,
-// ) -> Result<(), ApiError> {
-//     tracing::info!("Call previous on player: {player}");
-//
-//     let con = connection.as_ref();
-//
-//     let proxy = MprisPlayerProxy::new(con, player.clone())
-//         .await
-//         .with_context(|| {
-//             format!("Failed to create DBus Player2 connection MPRIS protocol for player: {player}")
-//         })
-//         .map_err(ApiError::PlaybackStatus)?;
-//
-//     proxy
-//         .previous()
-//         .map_err(|error| {
-//             ApiError::Previous(anyhow::anyhow!(
-//                 "Failed to call previous on player: {error}"
-//             ))
-//         })
-//         .await
-// }
+async fn next(
+    State(state): State<MediaState>,
+    PlayerName(player): PlayerName,
+) -> Result<(), ApiError> {
+    tracing::info!("Call next on player: {player}");
+    let con = state.connection.as_ref();
+
+    let proxy = MprisPlayerProxy::try_create(con, &*player)
+        .await
+        .map_err(ApiError::ConstructPlayer)?;
+    ensure_capability(proxy.can_go_next().await, &player, "CanGoNext")?;
+
+    proxy.next().await.map_err(|error| {
+        ApiError::Next(
+            anyhow::Error::new(error).context(format!("Failed to call Next on player: {player}")),
+        )
+    })?;
+
+    state.hub.refresh(&player);
+
+    Ok(())
+}
+
+async fn previous(
+    State(state): State<MediaState>,
+    PlayerName(player): PlayerName,
+) -> Result<(), ApiError> {
+    tracing::info!("Call previous on player: {player}");
+    let con = state.connection.as_ref();
+
+    let proxy = MprisPlayerProxy::try_create(con, &*player)
+        .await
+        .map_err(ApiError::ConstructPlayer)?;
+    ensure_capability(proxy.can_go_previous().await, &player, "CanGoPrevious")?;
+
+    proxy.previous().await.map_err(|error| {
+        ApiError::Previous(
+            anyhow::Error::new(error)
+                .context(format!("Failed to call Previous on player: {player}")),
+        )
+    })?;
+
+    state.hub.refresh(&player);
+
+    Ok(())
+}
@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+use zbus::names::BusName;
+use zbus::zvariant::{ObjectPath, OwnedObjectPath, OwnedValue};
+use zbus::{Connection, Result, proxy};
+
+use crate::media::player::{self, Metadata};
+
+/// Track id used as `AfterTrack` to add a track to the beginning of the track list.
+pub const NO_TRACK: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+
+#[proxy(
+    interface = "org.mpris.MediaPlayer2.TrackList",
+    default_path = "/org/mpris/MediaPlayer2"
+)]
+pub trait MprisTrackList {
+    fn get_tracks_metadata(
+        &self,
+        track_ids: &[ObjectPath<'_>],
+    ) -> Result<Vec<HashMap<String, OwnedValue>>>;
+    fn add_track(
+        &self,
+        uri: &str,
+        after_track: &ObjectPath<'_>,
+        set_as_current: bool,
+    ) -> Result<()>;
+    fn remove_track(&self, track_id: &ObjectPath<'_>) -> Result<()>;
+    fn go_to(&self, track_id: &ObjectPath<'_>) -> Result<()>;
+
+    #[zbus(property)]
+    fn tracks(&self) -> Result<Vec<OwnedObjectPath>>;
+
+    #[zbus(property)]
+    fn can_edit_tracks(&self) -> Result<bool>;
+
+    #[zbus(signal)]
+    fn track_list_replaced(
+        &self,
+        tracks: Vec<OwnedObjectPath>,
+        current_track: OwnedObjectPath,
+    ) -> Result<()>;
+
+    #[zbus(signal)]
+    fn track_added(
+        &self,
+        metadata: HashMap<String, OwnedValue>,
+        after_track: OwnedObjectPath,
+    ) -> Result<()>;
+
+    #[zbus(signal)]
+    fn track_removed(&self, track_id: OwnedObjectPath) -> Result<()>;
+
+    #[zbus(signal)]
+    fn track_metadata_changed(
+        &self,
+        track_id: OwnedObjectPath,
+        metadata: HashMap<String, OwnedValue>,
+    ) -> Result<()>;
+}
+
+impl MprisTrackListProxy<'static> {
+    pub async fn try_create<'a>(
+        connection: &Connection,
+        destination: impl TryInto<BusName<'a>> + Display + Send,
+    ) -> std::result::Result<Self, anyhow::Error> {
+        player::uncached_proxy(connection, destination, "TrackList").await
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TrackListReplacedEvent {
+    pub tracks: Vec<String>,
+    pub current_track: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TrackAddedEvent {
+    pub metadata: Metadata,
+    pub after_track: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TrackMetadataChangedEvent {
+    pub track_id: String,
+    pub metadata: Metadata,
+}
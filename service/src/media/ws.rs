@@ -0,0 +1,324 @@
+use std::collections::HashMap;
+
+use axum::extract::State;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::response::Response;
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::mpsc::{self, Sender};
+use tokio::task::JoinHandle;
+
+use crate::ApiError;
+use crate::extract::{Form, Query};
+use crate::media::hub::{PlayerEvent, PlayerState};
+use crate::media::routes::{self, MediaState, PlayerName};
+use crate::pulseaudio::VolumeForm;
+
+/// Message sent by the client. Every request carries an id of client's choosing, echoed back in
+/// the reply.
+///
+/// ```json
+/// {"type": "subscribe", "id": 1, "player": "firefox"}
+/// {"type": "seek", "id": 2, "player": "firefox", "offset": -10}
+/// {"type": "volume", "id": 3, "player": "firefox", "percent": 40}
+/// ```
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum Request {
+    Subscribe {
+        id: Value,
+        player: String,
+    },
+    Unsubscribe {
+        id: Value,
+        player: String,
+    },
+    Play {
+        id: Value,
+        player: String,
+    },
+    Pause {
+        id: Value,
+        player: String,
+    },
+    Seek {
+        id: Value,
+        player: String,
+        offset: i64,
+    },
+    Volume {
+        id: Value,
+        player: String,
+        percent: u32,
+    },
+}
+
+impl Request {
+    fn id(&self) -> &Value {
+        match self {
+            Request::Subscribe { id, .. }
+            | Request::Unsubscribe { id, .. }
+            | Request::Play { id, .. }
+            | Request::Pause { id, .. }
+            | Request::Seek { id, .. }
+            | Request::Volume { id, .. } => id,
+        }
+    }
+}
+
+/// Message sent to the client. Player is the player as given by the client in subscribe.
+#[derive(Serialize, Debug)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum Reply {
+    Ack {
+        id: Value,
+    },
+    /// Request failed, status and code are the HTTP status code and error code the same failure
+    /// has in the REST API.
+    Error {
+        id: Value,
+        status: u16,
+        code: &'static str,
+        message: String,
+    },
+    /// Full state of the player sent once subscribed.
+    State {
+        player: String,
+        state: Box<PlayerState>,
+    },
+    /// Changed fields of the player state, named as in the full state. Position only changes on
+    /// seek.
+    Delta {
+        player: String,
+        changes: serde_json::Map<String, Value>,
+    },
+    /// Player disappeared, no further deltas are sent for it.
+    Closed {
+        player: String,
+        reason: String,
+    },
+}
+
+impl Reply {
+    fn result(id: Value, result: Result<(), ApiError>) -> Self {
+        match result {
+            Ok(()) => Reply::Ack { id },
+            Err(error) => Reply::Error {
+                id,
+                status: error.status_code().as_u16(),
+                code: error.code(),
+                message: error.to_string(),
+            },
+        }
+    }
+
+    fn delta(player: &str, event: PlayerEvent) -> Self {
+        let (field, value) = match event {
+            PlayerEvent::Metadata(metadata) => ("metadata", serde_json::to_value(metadata)),
+            PlayerEvent::Status(status) => ("status", Ok(status.into())),
+            PlayerEvent::Seeked(position) | PlayerEvent::Position(position) => {
+                ("position", Ok(position.into()))
+            }
+            PlayerEvent::Volume(volume) => ("volume", Ok(volume.into())),
+            PlayerEvent::PlayerVolume(volume) => ("player_volume", Ok(volume.into())),
+            PlayerEvent::LoopStatus(loop_status) => ("loop_status", Ok(loop_status.into())),
+            PlayerEvent::Shuffle(shuffle) => ("shuffle", Ok(shuffle.into())),
+            PlayerEvent::Rate(rate) => ("rate", Ok(rate.into())),
+            PlayerEvent::Capabilities(capabilities) => {
+                ("capabilities", serde_json::to_value(capabilities))
+            }
+            PlayerEvent::Closed(reason) => {
+                return Reply::Closed {
+                    player: player.to_string(),
+                    reason,
+                };
+            }
+        };
+
+        Reply::Delta {
+            player: player.to_string(),
+            changes: serde_json::Map::from_iter([(
+                field.to_string(),
+                value.expect("player event should serialize to JSON"),
+            )]),
+        }
+    }
+}
+
+/// Subscriptions of a single WebSocket connection by player as given by the client.
+#[derive(Default)]
+struct Subscriptions(HashMap<String, JoinHandle<()>>);
+
+impl Drop for Subscriptions {
+    fn drop(&mut self) {
+        self.0.values().for_each(JoinHandle::abort);
+    }
+}
+
+pub async fn connect(State(state): State<MediaState>, upgrade: WebSocketUpgrade) -> Response {
+    tracing::info!("WebSocket connected");
+    upgrade.on_upgrade(|socket| handle(socket, state))
+}
+
+async fn handle(socket: WebSocket, state: MediaState) {
+    let (mut sink, mut stream) = socket.split();
+    let (tx, mut rx) = mpsc::channel::<Reply>(30);
+    let mut subscriptions = Subscriptions::default();
+
+    loop {
+        // replies are sent here directly instead of through the channel this loop is draining
+        let reply = tokio::select! {
+            Some(reply) = rx.recv() => reply,
+            message = stream.next() => {
+                let text = match message {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | None => break,
+                    Some(Ok(_)) => continue,
+                    Some(Err(error)) => {
+                        tracing::debug!("Failed to receive WebSocket message, closing: {error}");
+                        break;
+                    }
+                };
+
+                let reply = match serde_json::from_str::<Request>(&text) {
+                    Ok(request) => handle_request(&state, &tx, &mut subscriptions, request).await,
+                    Err(error) => Some(Reply::Error {
+                        id: Value::Null,
+                        status: 400,
+                        code: "invalid_request",
+                        message: format!("invalid request: {error}"),
+                    }),
+                };
+                let Some(reply) = reply else {
+                    continue;
+                };
+                reply
+            }
+        };
+
+        let reply = serde_json::to_string(&reply).expect("reply should serialize to JSON");
+        if let Err(error) = sink.send(Message::Text(reply.into())).await {
+            tracing::debug!("Failed to send WebSocket message, closing: {error}");
+            break;
+        }
+    }
+
+    tracing::info!("WebSocket disconnected");
+}
+
+/// Handle request, returns the reply unless the reply is sent once the command is done.
+async fn handle_request(
+    state: &MediaState,
+    tx: &Sender<Reply>,
+    subscriptions: &mut Subscriptions,
+    request: Request,
+) -> Option<Reply> {
+    tracing::debug!(?request, "Got WebSocket request");
+    let id = request.id().clone();
+    let connection = State(state.connection.clone());
+
+    match request {
+        Request::Subscribe { player, .. } => {
+            // subscription of a closed player has finished, subscribe again e.g. after restart
+            if subscriptions
+                .0
+                .get(&player)
+                .is_some_and(|task| !task.is_finished())
+            {
+                return Some(Reply::result(id, Ok(())));
+            }
+            let task = tokio::spawn(subscribe(state.clone(), tx.clone(), id, player.clone()));
+            subscriptions.0.insert(player, task);
+        }
+        Request::Unsubscribe { player, .. } => {
+            if let Some(task) = subscriptions.0.remove(&player) {
+                task.abort();
+            }
+            return Some(Reply::result(id, Ok(())));
+        }
+        Request::Play { player, .. } => {
+            let player = state.resolve_player(player).map(PlayerName);
+            reply_when_done(
+                tx,
+                id,
+                async move { routes::play(connection, player?).await },
+            );
+        }
+        Request::Pause { player, .. } => {
+            let player = state.resolve_player(player).map(PlayerName);
+            reply_when_done(
+                tx,
+                id,
+                async move { routes::pause(connection, player?).await },
+            );
+        }
+        Request::Seek { player, offset, .. } => {
+            let player = state.resolve_player(player).map(PlayerName);
+            let params = HashMap::from([("offset".to_string(), offset.to_string())]);
+            let state = State(state.clone());
+            reply_when_done(tx, id, async move {
+                routes::seek(state, player?, Query(params)).await
+            });
+        }
+        Request::Volume {
+            player, percent, ..
+        } => {
+            let player = state.resolve_player(player).map(PlayerName);
+            let volume = Form(VolumeForm { percent });
+            reply_when_done(tx, id, async move {
+                routes::set_player_volume(connection, player?, volume).await
+            });
+        }
+    }
+
+    None
+}
+
+/// Commands may take a while, reply once done without holding up other messages.
+fn reply_when_done(
+    tx: &Sender<Reply>,
+    id: Value,
+    command: impl Future<Output = Result<(), ApiError>> + Send + 'static,
+) {
+    let tx = tx.clone();
+    tokio::spawn(async move {
+        let result = command.await;
+        let _ = tx.send(Reply::result(id, result)).await;
+    });
+}
+
+/// Subscribe to the player, replies once subscribed and then sends the player state until the
+/// player closes. Position is only sent when the player seeks, clients are expected to advance it
+/// themselves by status and rate in between.
+async fn subscribe(state: MediaState, tx: Sender<Reply>, id: Value, player: String) {
+    let subscription = match state.resolve_player(player.clone()) {
+        Ok(name) => state.hub.subscribe(&name).await,
+        Err(error) => Err(error),
+    };
+    let mut subscription = match subscription {
+        Ok(subscription) => subscription,
+        Err(error) => {
+            let _ = tx.send(Reply::result(id, Err(error))).await;
+            return;
+        }
+    };
+
+    let reply = Reply::State {
+        player: player.clone(),
+        state: Box::new(subscription.state()),
+    };
+    if tx.send(Reply::result(id, Ok(()))).await.is_err() || tx.send(reply).await.is_err() {
+        return;
+    }
+
+    while let Some(event) = subscription.recv().await {
+        if matches!(event, PlayerEvent::Position(_)) {
+            continue;
+        }
+        let closed = matches!(event, PlayerEvent::Closed(_));
+        if tx.send(Reply::delta(&player, event)).await.is_err() || closed {
+            break;
+        }
+    }
+}
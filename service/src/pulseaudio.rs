@@ -1,11 +1,16 @@
+use std::process::Stdio;
+
 use anyhow::anyhow;
+use async_stream::stream;
 use async_trait::async_trait;
-use axum::Form;
-use futures::TryFutureExt;
+use futures::stream::BoxStream;
+use futures::{StreamExt, TryFutureExt};
 use serde::Deserialize;
+use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::process::Command;
 
 use crate::ApiError;
+use crate::extract::Form;
 
 pub struct PaCtl;
 
@@ -22,6 +27,8 @@ pub trait PulseAudio {
     async fn get_default_sink(&self) -> Result<String, anyhow::Error>;
     async fn get_default_sink_volume(&self) -> Result<u32, anyhow::Error>;
     async fn set_default_sink_volume(&self, volume: u32) -> Result<(), anyhow::Error>;
+    /// Stream yielding whenever a sink or the server (e.g. default sink) changes.
+    async fn subscribe_sink_changes(&self) -> Result<BoxStream<'static, ()>, anyhow::Error>;
 }
 
 #[async_trait]
@@ -108,6 +115,40 @@ impl PulseAudio for PaCtl {
             .await
             .map(|_| ())
     }
+
+    async fn subscribe_sink_changes(&self) -> Result<BoxStream<'static, ()>, anyhow::Error> {
+        let mut child = PaCtl::cmd()
+            .arg("subscribe")
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|error| {
+                anyhow!(format!(
+                    "Failed to call: {command} to subscribe events: {error}",
+                    command = Self::COMMAND
+                ))
+            })?;
+
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| anyhow!("Failed to get stdout of {}", Self::COMMAND))?;
+        let mut lines = BufReader::new(stdout).lines();
+
+        Ok(stream! {
+            // keep the subscribe process alive as long as the stream is alive
+            let _child = child;
+
+            while let Ok(Some(line)) = lines.next_line().await {
+                tracing::trace!("Got pulseaudio event: {line}");
+                // e.g. Event 'change' on sink #52, or Event 'change' on server #-1
+                if line.contains(" on sink ") || line.contains(" on server ") {
+                    yield ();
+                }
+            }
+        }
+        .boxed())
+    }
 }
 
 pub async fn get_volume() -> Result<String, ApiError> {
@@ -123,7 +164,7 @@ pub async fn get_volume() -> Result<String, ApiError> {
 
 #[derive(Deserialize)]
 pub struct VolumeForm {
-    percent: u32,
+    pub percent: u32,
 }
 
 pub async fn set_volume(Form(volume): Form<VolumeForm>) -> Result<(), ApiError> {